@@ -0,0 +1,245 @@
+use std::{
+    cmp::Ordering,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Write},
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use super::{advance, headless_simulation, par_runs, Density, Metric, RunReport};
+use crate::simulation::{Config, Experiment, Simulation, MAX_TYPES};
+
+/// Scores a candidate by driving its freshly created simulation for `ticks` ticks, higher is better
+pub trait Fitness: Sync {
+    fn evaluate(&self, simulation: &mut Simulation, ticks: u64) -> f64;
+}
+
+/// Built in fitness functions selectable from a manifest
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Objective {
+    ClusterCount,
+    MotionEntropy,
+    /// Overlap between the dense regions `window` ticks before the end and at the end
+    StructurePersistence { window: u64 },
+}
+
+impl Fitness for Objective {
+    fn evaluate(&self, simulation: &mut Simulation, ticks: u64) -> f64 {
+        match self {
+            Objective::ClusterCount => measure_after(Metric::ClusterCount, simulation, ticks),
+            Objective::MotionEntropy => measure_after(Metric::MotionEntropy, simulation, ticks),
+            Objective::StructurePersistence { window } => {
+                let window = (*window).min(ticks);
+                advance(simulation, ticks - window);
+                let before = Density::measure(simulation);
+                advance(simulation, window);
+                before.overlap(&Density::measure(simulation))
+            }
+        }
+    }
+}
+
+fn measure_after(metric: Metric, simulation: &mut Simulation, ticks: u64) -> f64 {
+    let report: RunReport = advance(simulation, ticks);
+    metric.measure(simulation, &report)
+}
+
+/// Genetic algorithm over `Config` parameters and interaction rules, loaded from JSON, e.g.
+/// ```json
+/// {
+///     "base": { "n_cells": 10000, "rules": { "n_types": 4, "strength": 0.05 } },
+///     "population": 24,
+///     "generations": 20,
+///     "ticks": 1000,
+///     "objective": { "structure_persistence": { "window": 200 } },
+///     "output": "evolution"
+/// }
+/// ```
+/// The best genomes are saved to `output` as experiment manifests after every generation.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Evolution {
+    /// Starting genome, also fixes everything that isn't evolved such as `n_types`
+    pub base: Config,
+    pub population: usize,
+    pub generations: usize,
+    /// Best candidates copied unchanged into the next generation
+    pub elite: usize,
+    /// Candidates compared when picking each parent
+    pub tournament: usize,
+    /// Chance of each gene mutating
+    pub mutation_rate: f32,
+    /// Largest mutation as a fraction of a gene's range
+    pub mutation_scale: f32,
+    /// Ticks each candidate is simulated for
+    pub ticks: u64,
+    pub threads_per_run: usize,
+    /// Total thread budget, 0 uses every core
+    pub threads: usize,
+    pub objective: Objective,
+    pub seed: u64,
+    /// Number of best genomes saved
+    pub keep: usize,
+    pub output: PathBuf,
+}
+
+impl Default for Evolution {
+    fn default() -> Self {
+        Self {
+            base: Config::default(),
+            population: 24,
+            generations: 20,
+            elite: 2,
+            tournament: 3,
+            mutation_rate: 0.2,
+            mutation_scale: 0.25,
+            ticks: 1000,
+            threads_per_run: 1,
+            threads: 0,
+            objective: Objective::ClusterCount,
+            seed: 0,
+            keep: 3,
+            output: "evolution".into(),
+        }
+    }
+}
+
+impl Evolution {
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = File::open(path)?;
+        Ok(serde_json::from_reader(BufReader::new(file))?)
+    }
+
+    pub fn run(&self) -> io::Result<()> {
+        self.run_with(&self.objective)
+    }
+
+    /// Evolves with a custom fitness function instead of `objective`
+    pub fn run_with(&self, fitness: &dyn Fitness) -> io::Result<()> {
+        fs::create_dir_all(&self.output)?;
+        let mut log = BufWriter::new(File::create(self.output.join("generations.csv"))?);
+        writeln!(log, "generation,best,mean")?;
+
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let mut population: Vec<Config> = (0..self.population.max(1))
+            .map(|i| match i {
+                0 => self.base,
+                _ => self.randomised(&mut rng),
+            })
+            .collect();
+
+        for generation in 0..self.generations {
+            let scores = par_runs(&population, self.threads, self.threads_per_run, |config| {
+                let score = fitness.evaluate(&mut headless_simulation(*config), self.ticks);
+                match score.is_nan() {
+                    true => f64::NEG_INFINITY,
+                    false => score,
+                }
+            });
+
+            let mut ranked: Vec<(f64, Config)> = scores.into_iter().zip(population).collect();
+            ranked.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+
+            let mean = ranked.iter().map(|(score, _)| score).sum::<f64>() / ranked.len() as f64;
+            writeln!(log, "{},{},{}", generation, ranked[0].0, mean)?;
+            log.flush()?;
+            tracing::info!("Generation {}: best {:.4} mean {:.4}", generation, ranked[0].0, mean);
+
+            self.save_best(&ranked)?;
+            population = self.next_generation(&ranked, &mut rng);
+        }
+
+        Ok(())
+    }
+
+    fn save_best(&self, ranked: &[(f64, Config)]) -> io::Result<()> {
+        for (i, (score, config)) in ranked.iter().take(self.keep).enumerate() {
+            let preset = Experiment {
+                name: format!("evolved {} (fitness {:.4})", i, score),
+                config: *config,
+                events: Vec::new(),
+            };
+            preset.save(self.output.join(format!("best_{}.json", i)))?;
+        }
+        Ok(())
+    }
+
+    fn next_generation(&self, ranked: &[(f64, Config)], rng: &mut StdRng) -> Vec<Config> {
+        let mut next: Vec<Config> = ranked.iter().take(self.elite).map(|(_, config)| *config).collect();
+
+        while next.len() < ranked.len() {
+            let a = self.select(ranked, rng);
+            let b = self.select(ranked, rng);
+            let mut child = crossover(a, b, rng);
+            self.mutate(&mut child, rng);
+            next.push(child);
+        }
+
+        next
+    }
+
+    /// Tournament selection, `ranked` is sorted best first so the lowest index wins
+    fn select<'a>(&self, ranked: &'a [(f64, Config)], rng: &mut StdRng) -> &'a Config {
+        let winner = (0..self.tournament.max(1))
+            .map(|_| rng.gen_range(0..ranked.len()))
+            .min()
+            .unwrap();
+        &ranked[winner].1
+    }
+
+    fn mutate(&self, config: &mut Config, rng: &mut StdRng) {
+        for (gene, range) in genes(config) {
+            if rng.gen::<f32>() < self.mutation_rate {
+                let span = range.end() - range.start();
+                *gene = (*gene + rng.gen_range(-1.0..=1.0) * self.mutation_scale * span)
+                    .clamp(*range.start(), *range.end());
+            }
+        }
+    }
+
+    /// Base genome with every gene drawn uniformly from its range
+    fn randomised(&self, rng: &mut StdRng) -> Config {
+        let mut config = self.base;
+        for (gene, range) in genes(&mut config) {
+            *gene = rng.gen_range(range);
+        }
+        config
+    }
+}
+
+/// Uniform crossover, each gene comes from either parent with equal odds
+fn crossover(a: &Config, b: &Config, rng: &mut StdRng) -> Config {
+    let mut child = *a;
+    let mut other = *b;
+    for ((gene, _), (other_gene, _)) in genes(&mut child).into_iter().zip(genes(&mut other)) {
+        if rng.gen_bool(0.5) {
+            *gene = *other_gene;
+        }
+    }
+    child
+}
+
+/// Every evolved value of a config with the range it may take
+fn genes(config: &mut Config) -> Vec<(&mut f32, RangeInclusive<f32>)> {
+    let rules = &mut config.rules;
+    let n_types = rules.n_types.min(MAX_TYPES);
+
+    let mut genes = vec![
+        (&mut config.cell_radius, 1.0..=4.0),
+        (&mut rules.interaction_radius, 8.0..=128.0),
+        (&mut rules.beta, 0.05..=0.6),
+        (&mut rules.strength, 0.005..=0.2),
+        (&mut rules.damping, 0.0..=0.5),
+    ];
+    for row in rules.attraction[..n_types].iter_mut() {
+        for attraction in row[..n_types].iter_mut() {
+            genes.push((attraction, -1.0..=1.0));
+        }
+    }
+
+    genes
+}
@@ -0,0 +1,60 @@
+use std::time::Instant;
+
+use rayon::{prelude::*, ThreadPool, ThreadPoolBuilder};
+
+use super::RunReport;
+use crate::simulation::{Config, Simulation};
+
+/// Simulation meant to be driven without a window
+pub fn headless_simulation(mut config: Config) -> Simulation {
+    // Nobody can rewind a headless run, so don't pay for snapshots
+    config.history_interval = 0;
+    Simulation::new(config)
+}
+
+/// Runs `ticks` ticks as fast as possible
+pub fn advance(simulation: &mut Simulation, ticks: u64) -> RunReport {
+    let start = Instant::now();
+    for _ in 0..ticks {
+        simulation.update();
+    }
+
+    RunReport {
+        ticks,
+        elapsed: start.elapsed(),
+    }
+}
+
+/// Runs a simulation without a window for `ticks` ticks
+pub fn run_headless(config: Config, ticks: u64) -> (Simulation, RunReport) {
+    let mut simulation = headless_simulation(config);
+    let report = advance(&mut simulation, ticks);
+    (simulation, report)
+}
+
+/// Pool that bounds the number of threads a single run's physics can use
+pub fn run_pool(threads: usize) -> ThreadPool {
+    ThreadPoolBuilder::new().num_threads(threads.max(1)).build().unwrap()
+}
+
+/// Maps `run` over `items` in parallel. Each call gets its own pool of `threads_per_run` threads,
+/// and as many calls run at once as fit in `threads` (0 uses every core)
+pub fn par_runs<T, R, F>(items: &[T], threads: usize, threads_per_run: usize, run: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let threads = match threads {
+        0 => rayon::current_num_threads(),
+        n => n,
+    };
+    let concurrent = (threads / threads_per_run.max(1)).max(1);
+
+    run_pool(concurrent).install(|| {
+        items
+            .par_iter()
+            .map(|item| run_pool(threads_per_run).install(|| run(item)))
+            .collect()
+    })
+}
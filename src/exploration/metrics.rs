@@ -0,0 +1,164 @@
+use std::time::Duration;
+
+use legion::*;
+use serde::{Deserialize, Serialize};
+
+use crate::simulation::{Config, RigidCircle, Simulation};
+
+/// Summary of a finished headless run
+pub struct RunReport {
+    pub ticks: u64,
+    pub elapsed: Duration,
+}
+
+/// Scalar measured on a simulation at the end of a run
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    TicksPerSecond,
+    EntityCount,
+    MeanSpeed,
+    /// Sum of `0.5 * v^2` over every particle, all particles have unit mass
+    KineticEnergy,
+    /// Connected groups of densely populated cells
+    ClusterCount,
+    /// Normalised entropy of the direction particles move in, 0 when all move alike, 1 when uniform
+    MotionEntropy,
+}
+
+impl Metric {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Metric::TicksPerSecond => "ticks_per_second",
+            Metric::EntityCount => "entity_count",
+            Metric::MeanSpeed => "mean_speed",
+            Metric::KineticEnergy => "kinetic_energy",
+            Metric::ClusterCount => "cluster_count",
+            Metric::MotionEntropy => "motion_entropy",
+        }
+    }
+
+    pub fn measure(&self, simulation: &Simulation, report: &RunReport) -> f64 {
+        match self {
+            Metric::TicksPerSecond => report.ticks as f64 / report.elapsed.as_secs_f64(),
+            Metric::EntityCount => simulation.world.len() as f64,
+            Metric::MeanSpeed => {
+                let n = simulation.world.len().max(1) as f64;
+                speeds(simulation).iter().sum::<f64>() / n
+            }
+            Metric::KineticEnergy => speeds(simulation).iter().map(|v| 0.5 * v * v).sum(),
+            Metric::ClusterCount => Density::measure(simulation).clusters() as f64,
+            Metric::MotionEntropy => motion_entropy(simulation),
+        }
+    }
+}
+
+fn speeds(simulation: &Simulation) -> Vec<f64> {
+    <&RigidCircle>::query()
+        .iter(&simulation.world)
+        .map(|circ| circ.vel.length() as f64)
+        .collect()
+}
+
+/// Number of direction bins used for motion entropy
+const DIRECTION_BINS: usize = 16;
+
+fn motion_entropy(simulation: &Simulation) -> f64 {
+    let mut bins = [0usize; DIRECTION_BINS];
+    <&RigidCircle>::query().for_each(&simulation.world, |circ| {
+        if circ.vel.length_squared() > 1e-8 {
+            let angle = circ.vel.y.atan2(circ.vel.x) as f64 + std::f64::consts::PI;
+            let bin = (angle / std::f64::consts::TAU * DIRECTION_BINS as f64) as usize;
+            bins[bin.min(DIRECTION_BINS - 1)] += 1;
+        }
+    });
+
+    let total = bins.iter().sum::<usize>().max(1) as f64;
+    let entropy: f64 = bins
+        .iter()
+        .filter(|count| **count > 0)
+        .map(|count| {
+            let p = *count as f64 / total;
+            -p * p.ln()
+        })
+        .sum();
+
+    entropy / (DIRECTION_BINS as f64).ln()
+}
+
+/// Which cells of a coarse grid over the world hold clearly more particles than average.
+/// Cells are one interaction radius wide
+pub struct Density {
+    side: usize,
+    dense: Vec<bool>,
+}
+
+impl Density {
+    pub fn measure(simulation: &Simulation) -> Self {
+        let config = *simulation.resources.get::<Config>().unwrap();
+        let (min, max) = config.bounds;
+        let cell = config.rules.interaction_radius.max(config.cell_radius * 4.0);
+        let side = (((max - min).max_element() / cell).ceil() as usize).max(1);
+
+        let mut counts = vec![0u32; side * side];
+        <&RigidCircle>::query().for_each(&simulation.world, |circ| {
+            let p = (circ.pos - min) / cell;
+            let x = (p.x.max(0.0) as usize).min(side - 1);
+            let y = (p.y.max(0.0) as usize).min(side - 1);
+            counts[y * side + x] += 1;
+        });
+
+        let mean = simulation.world.len() as f32 / counts.len() as f32;
+        let threshold = (2.0 * mean).max(4.0);
+
+        Self {
+            side,
+            dense: counts.iter().map(|count| *count as f32 >= threshold).collect(),
+        }
+    }
+
+    /// Number of 4-connected groups of dense cells
+    pub fn clusters(&self) -> usize {
+        let mut seen = vec![false; self.dense.len()];
+        let mut stack = Vec::new();
+        let mut clusters = 0;
+
+        for start in 0..self.dense.len() {
+            if !self.dense[start] || seen[start] {
+                continue;
+            }
+            clusters += 1;
+            seen[start] = true;
+            stack.push(start);
+
+            while let Some(ind) = stack.pop() {
+                let (x, y) = (ind % self.side, ind / self.side);
+                let neighbours = [
+                    (x > 0).then(|| ind - 1),
+                    (x + 1 < self.side).then(|| ind + 1),
+                    (y > 0).then(|| ind - self.side),
+                    (y + 1 < self.side).then(|| ind + self.side),
+                ];
+                for next in neighbours.iter().flatten() {
+                    if self.dense[*next] && !seen[*next] {
+                        seen[*next] = true;
+                        stack.push(*next);
+                    }
+                }
+            }
+        }
+
+        clusters
+    }
+
+    /// Jaccard similarity of the dense cells of two measurements, 0 if neither has any
+    pub fn overlap(&self, other: &Density) -> f64 {
+        let both = self.dense.iter().zip(&other.dense).filter(|(a, b)| **a && **b).count();
+        let either = self.dense.iter().zip(&other.dense).filter(|(a, b)| **a || **b).count();
+
+        match either {
+            0 => 0.0,
+            _ => both as f64 / either as f64,
+        }
+    }
+}
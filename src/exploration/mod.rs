@@ -0,0 +1,9 @@
+mod evolution;
+mod headless;
+mod metrics;
+mod sweep;
+
+pub use evolution::*;
+pub use headless::*;
+pub use metrics::*;
+pub use sweep::*;
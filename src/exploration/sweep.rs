@@ -0,0 +1,226 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{par_runs, run_headless, Metric};
+use crate::simulation::Config;
+
+/// Grid of headless runs over `Config` parameters, loaded from JSON, e.g.
+/// ```json
+/// {
+///     "base": { "n_cells": 20000 },
+///     "parameters": [
+///         { "name": "n_cells", "values": [10000, 20000, 40000] },
+///         { "name": "cell_radius", "values": { "start": 1.0, "end": 4.0, "steps": 4 } },
+///         { "name": "bounds.1", "values": [[4096, 4096], [8192, 8192]] }
+///     ],
+///     "ticks": 1000,
+///     "threads_per_run": 2,
+///     "metrics": ["ticks_per_second", "mean_speed"],
+///     "output": "sweep.csv"
+/// }
+/// ```
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Sweep {
+    pub base: Config,
+    pub parameters: Vec<Parameter>,
+    /// Runs per parameter combination, each with the next seed after `base.seed`
+    pub repeats: u32,
+    pub ticks: u64,
+    /// Rayon threads given to each run
+    pub threads_per_run: usize,
+    /// Total thread budget, 0 uses every core
+    pub threads: usize,
+    pub metrics: Vec<Metric>,
+    pub output: PathBuf,
+}
+
+impl Default for Sweep {
+    fn default() -> Self {
+        Self {
+            base: Config::default(),
+            parameters: Vec::new(),
+            repeats: 1,
+            ticks: 1000,
+            threads_per_run: 1,
+            threads: 0,
+            metrics: vec![Metric::TicksPerSecond, Metric::MeanSpeed],
+            output: "sweep.csv".into(),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Parameter {
+    /// Dotted path into `Config`, e.g. `n_cells` or `bounds.1`
+    pub name: String,
+    pub values: Values,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Values {
+    List(Vec<Value>),
+    /// `steps` evenly spaced values from `start` to `end` inclusive
+    Range { start: f64, end: f64, steps: usize },
+}
+
+impl Values {
+    pub fn expand(&self) -> Vec<Value> {
+        match self {
+            Values::List(values) => values.clone(),
+            Values::Range { start, end, steps } => (0..*steps)
+                .map(|i| {
+                    let t = match steps {
+                        1 => 0.0,
+                        _ => i as f64 / (steps - 1) as f64,
+                    };
+                    let v = start + (end - start) * t;
+                    // Whole numbers stay integers so they can fill integer fields
+                    match v.fract() == 0.0 {
+                        true => Value::from(v as i64),
+                        false => Value::from(v),
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+/// A single point of the sweep
+struct Run {
+    values: Vec<Value>,
+    repeat: u32,
+    config: Config,
+}
+
+impl Sweep {
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = File::open(path)?;
+        Ok(serde_json::from_reader(BufReader::new(file))?)
+    }
+
+    /// Executes every run and writes one row per run to `output`
+    pub fn run(&self) -> io::Result<()> {
+        let runs = self.runs()?;
+        let done = AtomicUsize::new(0);
+
+        let rows: Vec<Vec<f64>> = par_runs(&runs, self.threads, self.threads_per_run, |run| {
+            let (simulation, report) = run_headless(run.config, self.ticks);
+            let row = self
+                .metrics
+                .iter()
+                .map(|metric| metric.measure(&simulation, &report))
+                .collect();
+
+            let n_done = done.fetch_add(1, Ordering::Relaxed) + 1;
+            tracing::info!("[{}/{}] {}", n_done, runs.len(), self.describe(run));
+            row
+        });
+
+        self.write_table(&runs, &rows)
+    }
+
+    /// Cartesian product of every parameter's values, repeated `repeats` times
+    fn runs(&self) -> io::Result<Vec<Run>> {
+        let combinations = self.parameters.iter().fold(vec![Vec::new()], |acc, param| {
+            let values = param.values.expand();
+            acc.iter()
+                .flat_map(|prefix| {
+                    values.iter().map(move |value| {
+                        let mut combination: Vec<Value> = prefix.clone();
+                        combination.push(value.clone());
+                        combination
+                    })
+                })
+                .collect()
+        });
+
+        let mut runs = Vec::new();
+        for values in combinations {
+            for repeat in 0..self.repeats.max(1) {
+                let mut config = with_values(&self.base, &self.parameters, &values)?;
+                config.seed = self.base.seed + repeat as u64;
+                runs.push(Run {
+                    values: values.clone(),
+                    repeat,
+                    config,
+                });
+            }
+        }
+
+        Ok(runs)
+    }
+
+    fn describe(&self, run: &Run) -> String {
+        self.parameters
+            .iter()
+            .zip(&run.values)
+            .map(|(param, value)| format!("{}={}", param.name, value))
+            .chain(std::iter::once(format!("seed={}", run.config.seed)))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn write_table(&self, runs: &[Run], rows: &[Vec<f64>]) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(&self.output)?);
+
+        let header: Vec<String> = ["run", "repeat", "seed"]
+            .iter()
+            .map(|s| s.to_string())
+            .chain(self.parameters.iter().map(|param| csv_field(&param.name)))
+            .chain(self.metrics.iter().map(|metric| metric.name().to_string()))
+            .collect();
+        writeln!(out, "{}", header.join(","))?;
+
+        for (i, (run, row)) in runs.iter().zip(rows).enumerate() {
+            let fields: Vec<String> = [i.to_string(), run.repeat.to_string(), run.config.seed.to_string()]
+                .iter()
+                .cloned()
+                .chain(run.values.iter().map(|value| csv_field(&value.to_string())))
+                .chain(row.iter().map(|metric| metric.to_string()))
+                .collect();
+            writeln!(out, "{}", fields.join(","))?;
+        }
+
+        out.flush()
+    }
+}
+
+/// Copy of `base` with each parameter path set to its value
+fn with_values(base: &Config, parameters: &[Parameter], values: &[Value]) -> io::Result<Config> {
+    let mut config = serde_json::to_value(base)?;
+
+    for (param, value) in parameters.iter().zip(values) {
+        let slot = param
+            .name
+            .split('.')
+            .try_fold(&mut config, |node, key| match key.parse::<usize>() {
+                Ok(index) => node.get_mut(index),
+                Err(_) => node.get_mut(key),
+            })
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Unknown config parameter {}", param.name),
+                )
+            })?;
+        *slot = value.clone();
+    }
+
+    Ok(serde_json::from_value(config)?)
+}
+
+fn csv_field(field: &str) -> String {
+    match field.contains(',') || field.contains('"') {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.to_string(),
+    }
+}
@@ -1,3 +1,4 @@
+pub mod exploration;
 pub mod rendering;
 pub mod simulation;
 pub mod timing;
@@ -8,15 +9,28 @@ use winit::{
 };
 
 use crate::{
+    exploration::{Evolution, Sweep},
     rendering::RenderDriver,
-    simulation::{Config, Simulation},
+    simulation::{Config, Experiment, Simulation},
 };
 
 fn main() {
     wgpu_subscriber::initialize_default_subscriber(None);
 
-    let config = Config::default();
-    let mut simulation = Simulation::new(config);
+    let args: Vec<String> = std::env::args().collect();
+
+    // `petri sweep <manifest>` and `petri evolve <manifest>` run headless and exit
+    match (args.get(1).map(String::as_str), args.get(2)) {
+        (Some("sweep"), Some(path)) => return Sweep::load(path).unwrap().run().unwrap(),
+        (Some("evolve"), Some(path)) => return Evolution::load(path).unwrap().run().unwrap(),
+        _ => {}
+    }
+
+    // Otherwise an experiment manifest may be passed as the first argument
+    let mut simulation = match args.get(1) {
+        Some(path) => Simulation::from_experiment(&Experiment::load(path).unwrap()),
+        None => Simulation::new(Config::default()),
+    };
 
     let event_loop = EventLoop::new();
     let mut renderer = RenderDriver::new(&mut simulation, &event_loop);
@@ -29,7 +43,7 @@ fn main() {
 
         match event {
             // Rendering
-            RedrawRequested(..) => renderer.render(&simulation),
+            RedrawRequested(..) => renderer.render(&mut simulation),
             // Updating simulation and queuing a redraw
             MainEventsCleared => {
                 simulation.update();
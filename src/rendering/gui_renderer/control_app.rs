@@ -0,0 +1,138 @@
+use crate::{
+    rendering::Display,
+    simulation::{Simulation, Time},
+};
+
+pub struct ControlApp {
+    /// Ticks queued by the step button
+    step_count: u32,
+    /// Last rate chosen while the limiter was on
+    tick_rate: u32,
+    /// Tick selected on the rewind slider
+    rewind_tick: u64,
+    /// Last interval chosen while history was recorded
+    history_interval: u32,
+}
+
+impl Default for ControlApp {
+    fn default() -> Self {
+        Self {
+            step_count: 1,
+            tick_rate: 60,
+            rewind_tick: 0,
+            history_interval: 10,
+        }
+    }
+}
+
+impl ControlApp {
+    pub fn update(&mut self, ctx: &egui::CtxRef, _display: &Display, simulation: &mut Simulation) {
+        let (mut limited, mut time_scale) = {
+            let time = simulation.resources.get::<Time>().unwrap();
+            if let Some(rate) = time.target_tick_rate {
+                self.tick_rate = rate;
+            }
+            (time.target_tick_rate.is_some(), time.time_scale)
+        };
+
+        egui::Window::new("Controls").resizable(false).show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                let label = match simulation.is_paused() {
+                    true => "Resume",
+                    false => "Pause",
+                };
+                if ui.button(label).clicked() {
+                    simulation.toggle_pause();
+                }
+                if ui.button("Step").clicked() {
+                    simulation.step(self.step_count);
+                }
+                ui.add(egui::DragValue::new(&mut self.step_count).clamp_range(1..=10_000).suffix(" ticks"));
+            });
+
+            ui.separator();
+
+            ui.checkbox(&mut limited, "Limit tick rate");
+            ui.scope(|ui| {
+                ui.set_enabled(limited);
+                ui.add(
+                    egui::Slider::new(&mut self.tick_rate, 1..=1000)
+                        .logarithmic(true)
+                        .text("Target ticks / s"),
+                );
+            });
+            simulation.set_target_tick_rate(match limited {
+                true => Some(self.tick_rate),
+                false => None,
+            });
+
+            let mut adaptive = simulation.adaptive_timestep();
+            if ui.checkbox(&mut adaptive, "Adaptive time step").changed() {
+                simulation.set_adaptive_timestep(adaptive);
+            }
+            ui.scope(|ui| {
+                ui.set_enabled(!adaptive);
+                ui.add(
+                    egui::Slider::new(&mut time_scale, 0.01..=10.0)
+                        .logarithmic(true)
+                        .text("Time scale"),
+                );
+            });
+            simulation.set_time_scale(time_scale);
+
+            let mut auto_tune = simulation.auto_tune_grid();
+            if ui.checkbox(&mut auto_tune, "Auto-tune grid cell size").changed() {
+                simulation.set_auto_tune_grid(auto_tune);
+            }
+
+            ui.separator();
+            self.rewind(ui, simulation);
+        });
+    }
+
+    fn rewind(&mut self, ui: &mut egui::Ui, simulation: &mut Simulation) {
+        let mut recording = simulation.history_interval() > 0;
+        if recording {
+            self.history_interval = simulation.history_interval();
+        }
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut recording, "Record history");
+            ui.scope(|ui| {
+                ui.set_enabled(recording);
+                ui.add(
+                    egui::DragValue::new(&mut self.history_interval)
+                        .clamp_range(1..=10_000)
+                        .prefix("every ")
+                        .suffix(" ticks"),
+                );
+            });
+        });
+        simulation.set_history_interval(match recording {
+            true => self.history_interval,
+            false => 0,
+        });
+
+        let history = &simulation.history;
+        ui.label(format!(
+            "History: {} snapshots ({:.1} MiB)",
+            history.len(),
+            history.bytes() as f64 / (1024.0 * 1024.0)
+        ));
+
+        let (first, last) = match history.tick_range() {
+            Some((first, last)) => (first as u64, last as u64),
+            None => return,
+        };
+        self.rewind_tick = self.rewind_tick.clamp(first, last);
+
+        ui.horizontal(|ui| {
+            if ui.button("Back").clicked() {
+                simulation.step_back();
+            }
+            ui.add(egui::Slider::new(&mut self.rewind_tick, first..=last).text("Tick"));
+            if ui.button("Jump").clicked() {
+                simulation.rewind_to(self.rewind_tick as u128);
+            }
+        });
+    }
+}
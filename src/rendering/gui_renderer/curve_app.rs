@@ -0,0 +1,165 @@
+use egui::{epaint::Color32, Align2, Pos2, Sense, Shape, Stroke, TextStyle};
+use glam::{vec2, Vec2};
+
+use crate::{
+    rendering::Display,
+    simulation::{Config, ForceCurve, Interpolation, Simulation, MAX_TYPES},
+};
+
+/// Pixels from a control point within which the pointer grabs it
+const GRAB_RADIUS: f32 = 8.0;
+/// Line segments the curve is drawn with
+const CURVE_SEGMENTS: usize = 200;
+
+/// Plot of the force curve between a pair of types whose control points can be dragged around
+pub struct CurveApp {
+    from: u8,
+    to: u8,
+    /// Distance at the right edge of the plot
+    max_dist: f32,
+    /// Force at the top edge of the plot, the bottom is its negative
+    max_force: f32,
+    /// Control point being dragged
+    dragging: Option<usize>,
+}
+
+impl Default for CurveApp {
+    fn default() -> Self {
+        Self {
+            from: 0,
+            to: 0,
+            max_dist: 64.0,
+            max_force: 1.0,
+            dragging: None,
+        }
+    }
+}
+
+impl CurveApp {
+    pub fn update(&mut self, ctx: &egui::CtxRef, _display: &Display, simulation: &mut Simulation) {
+        let n_types = simulation.resources.get::<Config>().unwrap().rules.n_types;
+        let n_types = n_types.clamp(1, MAX_TYPES) as u8;
+        self.from = self.from.min(n_types - 1);
+        self.to = self.to.min(n_types - 1);
+
+        egui::Window::new("Force Curves").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                type_picker(ui, "Force on", &mut self.from, n_types);
+                type_picker(ui, "from", &mut self.to, n_types);
+            });
+
+            let mut curve = simulation.force_curve(self.from, self.to);
+            let before = curve;
+
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut curve.interpolation, Interpolation::Linear, "Linear");
+                ui.radio_value(&mut curve.interpolation, Interpolation::Cubic, "Cubic");
+                if ui.button("Clear").clicked() {
+                    curve = ForceCurve::default();
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.add(egui::DragValue::new(&mut self.max_dist).clamp_range(1.0..=4096.0).prefix("Distance: "));
+                ui.add(
+                    egui::DragValue::new(&mut self.max_force)
+                        .speed(0.01)
+                        .clamp_range(0.01..=1000.0)
+                        .prefix("Force: ±"),
+                );
+            });
+
+            self.plot(ui, &mut curve);
+            ui.label("Drag points to move them, click to add one, right click to remove one");
+
+            if curve != before {
+                simulation.set_force_curve(self.from, self.to, curve);
+            }
+        });
+    }
+
+    /// Draws `curve` and applies any edits made with the pointer
+    fn plot(&mut self, ui: &mut egui::Ui, curve: &mut ForceCurve) {
+        let (response, painter) = ui.allocate_painter(egui::vec2(360.0, 220.0), Sense::click_and_drag());
+        let rect = response.rect;
+        let (max_dist, max_force) = (self.max_dist, self.max_force);
+
+        let to_screen = |p: Vec2| {
+            Pos2::new(
+                rect.left() + p.x / max_dist * rect.width(),
+                rect.center().y - p.y / max_force * 0.5 * rect.height(),
+            )
+        };
+        let from_screen = |p: Pos2| {
+            vec2(
+                (p.x - rect.left()) / rect.width() * max_dist,
+                (rect.center().y - p.y) / (0.5 * rect.height()) * max_force,
+            )
+        };
+        let grabbed = |pointer: Option<Pos2>, curve: &ForceCurve| {
+            let pointer = pointer?;
+            curve
+                .points()
+                .iter()
+                .map(|p| to_screen(*p).distance(pointer))
+                .enumerate()
+                .filter(|(_, dist)| *dist < GRAB_RADIUS)
+                .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+                .map(|(i, _)| i)
+        };
+
+        if response.drag_started() {
+            self.dragging = grabbed(response.interact_pointer_pos(), curve);
+        }
+        match (response.dragged(), self.dragging, response.interact_pointer_pos()) {
+            (true, Some(ind), Some(pointer)) => self.dragging = curve.move_point(ind, from_screen(pointer)),
+            (true, ..) => {}
+            (false, ..) => self.dragging = None,
+        }
+        if response.clicked() && grabbed(response.interact_pointer_pos(), curve).is_none() {
+            if let Some(pointer) = response.interact_pointer_pos() {
+                curve.insert(from_screen(pointer));
+            }
+        }
+        if response.secondary_clicked() {
+            if let Some(ind) = grabbed(response.interact_pointer_pos(), curve) {
+                curve.remove(ind);
+            }
+        }
+
+        painter.rect_filled(rect, 0.0, Color32::from_gray(16));
+        painter.line_segment(
+            [to_screen(vec2(0.0, 0.0)), to_screen(vec2(max_dist, 0.0))],
+            Stroke::new(1.0, Color32::from_gray(80)),
+        );
+        painter.text(rect.left_top(), Align2::LEFT_TOP, "attract", TextStyle::Small, Color32::GRAY);
+        painter.text(rect.left_bottom(), Align2::LEFT_BOTTOM, "repel", TextStyle::Small, Color32::GRAY);
+
+        let line = (0..=CURVE_SEGMENTS)
+            .map(|i| {
+                let dist = i as f32 / CURVE_SEGMENTS as f32 * max_dist;
+                let force = curve.eval(dist).clamp(-max_force, max_force);
+                to_screen(vec2(dist, force))
+            })
+            .collect();
+        painter.add(Shape::line(line, Stroke::new(1.5, Color32::LIGHT_BLUE)));
+
+        let hovered = self.dragging.or_else(|| grabbed(response.hover_pos(), curve));
+        for (i, point) in curve.points().iter().enumerate() {
+            let color = match hovered == Some(i) {
+                true => Color32::YELLOW,
+                false => Color32::WHITE,
+            };
+            painter.circle_filled(to_screen(*point), 4.0, color);
+        }
+    }
+}
+
+fn type_picker(ui: &mut egui::Ui, label: &str, kind: &mut u8, n_types: u8) {
+    egui::ComboBox::from_label(label)
+        .selected_text(format!("Type {}", kind))
+        .show_ui(ui, |ui| {
+            for i in 0..n_types {
+                ui.selectable_value(kind, i, format!("Type {}", i));
+            }
+        });
+}
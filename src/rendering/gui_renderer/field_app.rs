@@ -0,0 +1,155 @@
+use egui::{epaint::Color32, LayerId, Pos2, Stroke};
+use glam::{vec2, Vec2};
+
+use crate::{
+    rendering::Display,
+    simulation::{Falloff, Field, FieldKind, Simulation},
+};
+
+/// Screen radius of the dot marking a field's position
+const MARKER_RADIUS: f32 = 4.0;
+/// Screen length of the line showing a field's direction
+const DIRECTION_LENGTH: f32 = 24.0;
+
+/// List of the external fields with their settings. The selected one follows the pointer while
+/// the right mouse button is held over the simulation
+pub struct FieldApp {
+    selected: Option<usize>,
+    /// Kind the add button creates
+    new_kind: FieldKind,
+}
+
+impl Default for FieldApp {
+    fn default() -> Self {
+        Self {
+            selected: None,
+            new_kind: FieldKind::Attractor,
+        }
+    }
+}
+
+impl FieldApp {
+    pub fn update(&mut self, ctx: &egui::CtxRef, display: &Display, simulation: &mut Simulation) {
+        let mut fields = simulation.fields();
+        let before = fields;
+
+        egui::Window::new("Fields").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                kind_picker(ui, "New", &mut self.new_kind);
+                if ui.button("Add").clicked() {
+                    let field = Field {
+                        kind: self.new_kind,
+                        pos: display.cam.pos(),
+                        ..Default::default()
+                    };
+                    self.selected = fields.push(field).or(self.selected);
+                }
+            });
+
+            ui.horizontal_wrapped(|ui| {
+                for (i, field) in fields.fields().iter().enumerate() {
+                    if ui
+                        .selectable_label(self.selected == Some(i), format!("{} {:?}", i, field.kind))
+                        .clicked()
+                    {
+                        self.selected = Some(i);
+                    }
+                }
+            });
+            self.selected = self.selected.filter(|&i| i < fields.fields().len());
+
+            if let Some(ind) = self.selected {
+                ui.separator();
+                edit(ui, &mut fields.fields_mut()[ind]);
+                if ui.button("Remove").clicked() {
+                    fields.remove(ind);
+                    self.selected = None;
+                }
+            }
+            ui.label("Hold the right mouse button to move the selected field to the pointer");
+        });
+
+        if let Some(ind) = self.selected {
+            if display.mouse.buttons[1].held && !ctx.is_pointer_over_area() {
+                fields.fields_mut()[ind].pos = display.cam.screen2world(display.mouse.pos);
+            }
+        }
+        self.draw_markers(ctx, display, fields.fields());
+
+        if fields != before {
+            simulation.set_fields(fields);
+        }
+    }
+
+    /// Marks every field's position over the simulation, with its radius and direction where
+    /// they matter
+    fn draw_markers(&self, ctx: &egui::CtxRef, display: &Display, fields: &[Field]) {
+        let painter = ctx.layer_painter(LayerId::background());
+        let scale = display.window.scale_factor() as f32;
+        let to_screen = |p: Vec2| {
+            let p = display.cam.world2screen(p) / scale;
+            Pos2::new(p.x, p.y)
+        };
+
+        for (i, field) in fields.iter().enumerate() {
+            let color = match self.selected == Some(i) {
+                true => Color32::YELLOW,
+                false => Color32::WHITE,
+            };
+            let centre = to_screen(field.pos);
+            painter.circle_filled(centre, MARKER_RADIUS, color);
+            if field.falloff != Falloff::None {
+                painter.circle_stroke(centre, field.radius * display.cam.zoom / scale, Stroke::new(1.0, color));
+            }
+            if matches!(field.kind, FieldKind::Gravity | FieldKind::Shear) {
+                let direction = field.direction.normalize_or_zero() * vec2(1.0, -1.0);
+                let tip = centre + egui::vec2(direction.x, direction.y) * DIRECTION_LENGTH;
+                painter.line_segment([centre, tip], Stroke::new(1.5, color));
+            }
+        }
+    }
+}
+
+/// Widgets for every setting of `field`
+fn edit(ui: &mut egui::Ui, field: &mut Field) {
+    kind_picker(ui, "Kind", &mut field.kind);
+    ui.horizontal(|ui| {
+        ui.add(egui::DragValue::new(&mut field.pos.x).prefix("x: "));
+        ui.add(egui::DragValue::new(&mut field.pos.y).prefix("y: "));
+    });
+    ui.add(egui::DragValue::new(&mut field.strength).speed(0.001).prefix("Strength: "));
+    egui::ComboBox::from_label("Falloff")
+        .selected_text(format!("{:?}", field.falloff))
+        .show_ui(ui, |ui| {
+            ui.selectable_value(&mut field.falloff, Falloff::None, "None");
+            ui.selectable_value(&mut field.falloff, Falloff::Linear, "Linear");
+            ui.selectable_value(&mut field.falloff, Falloff::InverseSquare, "InverseSquare");
+            ui.selectable_value(&mut field.falloff, Falloff::Gaussian, "Gaussian");
+        });
+    ui.add(
+        egui::DragValue::new(&mut field.radius)
+            .clamp_range(1.0..=1e6)
+            .prefix("Radius: "),
+    );
+    if matches!(field.kind, FieldKind::Gravity | FieldKind::Shear) {
+        let mut angle = field.direction.y.atan2(field.direction.x);
+        ui.horizontal(|ui| {
+            ui.label("Direction:");
+            if ui.drag_angle(&mut angle).changed() {
+                field.direction = vec2(angle.cos(), angle.sin());
+            }
+        });
+    }
+}
+
+fn kind_picker(ui: &mut egui::Ui, label: &str, kind: &mut FieldKind) {
+    egui::ComboBox::from_label(label)
+        .selected_text(format!("{:?}", kind))
+        .show_ui(ui, |ui| {
+            ui.selectable_value(kind, FieldKind::Gravity, "Gravity");
+            ui.selectable_value(kind, FieldKind::Attractor, "Attractor");
+            ui.selectable_value(kind, FieldKind::Vortex, "Vortex");
+            ui.selectable_value(kind, FieldKind::Shear, "Shear");
+            ui.selectable_value(kind, FieldKind::Noise, "Noise");
+        });
+}
@@ -5,7 +5,7 @@ use egui_wgpu_backend::{RenderPass, ScreenDescriptor};
 use egui_winit_platform::{Platform, PlatformDescriptor};
 use wgpu::TextureView;
 
-use super::{GridApp, PerfApp, StatApp};
+use super::{ControlApp, CurveApp, FieldApp, GridApp, PerfApp, StatApp};
 use crate::{
     rendering::{Display, PetriEventHandler},
     simulation::Simulation,
@@ -19,6 +19,9 @@ pub struct GUIRenderer {
     debug: StatApp,
     grid: GridApp,
     perf: PerfApp,
+    control: ControlApp,
+    curves: CurveApp,
+    fields: FieldApp,
 }
 
 impl GUIRenderer {
@@ -41,13 +44,16 @@ impl GUIRenderer {
             rpass: egui_rpass,
             start_time: Instant::now(),
             previous_frame_time: None,
-            debug: StatApp,
+            debug: StatApp::default(),
             grid: GridApp::default(),
             perf: PerfApp,
+            control: ControlApp::default(),
+            curves: CurveApp::default(),
+            fields: FieldApp::default(),
         }
     }
 
-    pub fn render(&mut self, display: &Display, simulation: &Simulation, view: &TextureView) {
+    pub fn render(&mut self, display: &Display, simulation: &mut Simulation, view: &TextureView) {
         self.platform.update_time(self.start_time.elapsed().as_secs_f64());
 
         // Begin to draw the UI frame.
@@ -57,6 +63,9 @@ impl GUIRenderer {
         self.grid.update(&self.platform.context(), display, simulation);
         self.debug.update(&self.platform.context(), display, simulation);
         self.perf.update(&self.platform.context(), display, simulation);
+        self.control.update(&self.platform.context(), display, simulation);
+        self.curves.update(&self.platform.context(), display, simulation);
+        self.fields.update(&self.platform.context(), display, simulation);
 
         // End the UI frame. We could now handle the output and draw the UI with the backend.
         let (_output, paint_commands) = self.platform.end_frame(Some(&display.window));
@@ -1,8 +1,14 @@
+mod control_app;
+mod curve_app;
+mod field_app;
 mod graph_app;
 pub mod gui_renderer;
 mod perf_app;
 mod stat_app;
 
+pub use control_app::ControlApp;
+pub use curve_app::CurveApp;
+pub use field_app::FieldApp;
 pub use graph_app::GridApp;
 pub use gui_renderer::GUIRenderer;
 pub use perf_app::PerfApp;
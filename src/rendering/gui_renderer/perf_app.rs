@@ -10,6 +10,7 @@ impl PerfApp {
             ui.heading("Performance");
             self.physics(ui);
             self.sim_render(ui);
+            self.history(ui);
         });
     }
 
@@ -22,11 +23,54 @@ impl PerfApp {
         ui.label(format!("Full Step {}", database.physics.step.res_str));
         ui.label(format!("\t{}", database.physics.step));
 
+        ui.label(format!("Substep {}", database.physics.substep.res_str));
+        ui.label(format!("\t{}", database.physics.substep));
+
+        ui.label(format!("Reorder {}", database.physics.reorder.res_str));
+        ui.label(format!("\t{}", database.physics.reorder));
+
+        ui.label(format!("Before Reorder {}", database.physics.before_reorder.res_str));
+        ui.label(format!("\t{}", database.physics.before_reorder));
+
+        ui.label(format!("After Reorder {}", database.physics.after_reorder.res_str));
+        ui.label(format!("\t{}", database.physics.after_reorder));
+
         ui.label(format!("Pos Update {}", database.physics.pos_update.res_str));
         ui.label(format!("\t{}", database.physics.pos_update));
 
+        ui.label(format!("Index Build {}", database.physics.index_build.res_str));
+        ui.label(format!("\t{}", database.physics.index_build));
+
+        ui.label(format!("Neighbour Lists {}", database.physics.neighbour_list.res_str));
+        ui.label(format!("\t{}", database.physics.neighbour_list));
+
+        ui.label(format!("CCD {}", database.physics.ccd.res_str));
+        ui.label(format!("\t{}", database.physics.ccd));
+
+        ui.label(format!("Fields {}", database.physics.fields.res_str));
+        ui.label(format!("\t{}", database.physics.fields));
+
+        ui.label(format!("Forces {}", database.physics.forces.res_str));
+        ui.label(format!("\t{}", database.physics.forces));
+
+        ui.label(format!("Potentials {}", database.physics.potentials.res_str));
+        ui.label(format!("\t{}", database.physics.potentials));
+
+        ui.label(format!("Long Range {}", database.physics.long_range.res_str));
+        ui.label(format!("\t{}", database.physics.long_range));
+
         ui.label(format!("Col Detection {}", database.physics.col_detect.res_str));
         ui.label(format!("\t{}", database.physics.col_detect));
+
+        ui.label(format!("Contact Solver {}", database.physics.contact_solve.res_str));
+        ui.label(format!("\t{}", database.physics.contact_solve));
+
+        ui.label(format!("Thermostat {}", database.physics.thermostat.res_str));
+        ui.label(format!("\t{}", database.physics.thermostat));
+
+        ui.label(format!("World Sync {}", database.physics.gather.res_str));
+        ui.label(format!("\tgather {}", database.physics.gather));
+        ui.label(format!("\tscatter {}", database.physics.scatter));
     }
 
     fn sim_render(&self, ui: &mut egui::Ui) {
@@ -44,4 +88,17 @@ impl PerfApp {
         ui.label(format!("Render {}", database.sim_render.render.res_str));
         ui.label(format!("\t{}", database.sim_render.render));
     }
+
+    fn history(&self, ui: &mut egui::Ui) {
+        let database = TIMING_DATABASE.read();
+
+        ui.separator();
+        ui.heading("History");
+
+        ui.label(format!("Capture {}", database.history.capture.res_str));
+        ui.label(format!("\t{}", database.history.capture));
+
+        ui.label(format!("Restore {}", database.history.restore.res_str));
+        ui.label(format!("\t{}", database.history.restore));
+    }
 }
@@ -1,22 +1,61 @@
+use legion::{Entity, EntityStore};
+
 use crate::{
     rendering::Display,
-    simulation::{Simulation, Time},
+    simulation::{RigidCircle, Simulation, Time},
 };
 
 #[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
-pub struct StatApp;
+#[derive(Default)]
+pub struct StatApp {
+    /// Particle picked with the middle mouse button, shown until it is removed
+    selected: Option<Entity>,
+    /// Whether the middle button was held last frame, so each click picks once
+    was_held: bool,
+}
 
 impl StatApp {
-    pub fn update(&mut self, ctx: &egui::CtxRef, display: &Display, simulation: &Simulation) {
-        let time = simulation.resources.get::<Time>().unwrap();
+    pub fn update(&mut self, ctx: &egui::CtxRef, display: &Display, simulation: &mut Simulation) {
         let cam = &display.cam;
+        let mouse = cam.screen2world(display.mouse.pos);
+        // Looking up the nearest particle rebuilds the query index after every tick, so only on click
+        let held = display.mouse.buttons[2].held;
+        if held && !self.was_held && !ctx.is_pointer_over_area() {
+            self.selected = simulation.spatial_query().nearest(mouse);
+        }
+        self.was_held = held;
+
+        let selected = self
+            .selected
+            .and_then(|entity| simulation.world.entry_ref(entity).ok())
+            .and_then(|entry| entry.get_component::<RigidCircle>().ok().copied());
+        let time = simulation.resources.get::<Time>().unwrap();
 
         egui::SidePanel::left("Debug Info").show(ctx, |ui| {
             ui.style_mut().wrap = Some(false);
             ui.heading("Stats");
             ui.label(format!("Time: {:.2}", time.time_since_start().as_secs_f32()));
             ui.label(format!("Ticks / s: {:}", time.tick_rate));
+            match time.target_tick_rate {
+                Some(rate) => ui.label(format!("Target Ticks / s: {}", rate)),
+                None => ui.label("Target Ticks / s: Unlimited"),
+            };
+            ui.label(format!("Sim Time: {:.2}", time.sim_time));
+            ui.label(format!("dt: {:.4}", time.dt()));
+            ui.label(format!("Paused: {}", simulation.is_paused()));
             ui.label(format!("Entities: {}", simulation.world.len()));
+            ui.label(format!("Temperature: {:.3}", simulation.physics.temperature()));
+            ui.label(format!("Tunnelling Avoided: {}", simulation.physics.tunnelling_avoided()));
+
+            ui.separator();
+
+            let tuner = simulation.physics.tuner();
+            ui.heading("Grid");
+            match tuner.is_tuning() {
+                true => ui.label(format!("Cell Size: {} (tuning)", tuner.cell_size)),
+                false => ui.label(format!("Cell Size: {}", tuner.cell_size)),
+            };
+            ui.label(format!("Candidates / Query: {:.1}", tuner.candidates));
 
             ui.separator();
 
@@ -27,7 +66,23 @@ impl StatApp {
                 cam.window_size.x, cam.window_size.y
             ));
             ui.label(format!("Zoom Factor: ({:.2})", cam.zoom));
-            ui.label(format!("Mouse Position: ({})", cam.screen2world(display.mouse.pos)));
+            ui.label(format!("Mouse Position: ({})", mouse));
+
+            ui.separator();
+
+            ui.heading("Selected Particle");
+            match selected {
+                Some(circ) => {
+                    ui.label(format!("Position: ({:.2}, {:.2})", circ.pos.x, circ.pos.y));
+                    ui.label(format!("Velocity: ({:.2}, {:.2})", circ.vel.x, circ.vel.y));
+                    ui.label(format!("Angular Velocity: {:.3}", circ.angular_vel));
+                    ui.label(format!("Radius: {:.2}", circ.radius));
+                    ui.label(format!("Type: {}", circ.kind));
+                }
+                None => {
+                    ui.label("Middle click to select the nearest particle");
+                }
+            }
         });
     }
 }
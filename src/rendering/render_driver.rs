@@ -35,7 +35,7 @@ impl RenderDriver {
         self.display.handle_event(event);
     }
 
-    pub fn render(&mut self, simulation: &Simulation) {
+    pub fn render(&mut self, simulation: &mut Simulation) {
         let (_output_frame, output_view) = self.display.get_frame().unwrap();
 
         self.sim_renderer.render(&self.display, simulation, &output_view);
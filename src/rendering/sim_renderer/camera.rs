@@ -33,4 +33,8 @@ impl Camera {
     pub fn screen2world(&self, p: Vec2) -> Vec2 {
         self.pos() + ((p - (self.window_size / 2.0)) * vec2(1.0, -1.0)) / self.zoom
     }
+
+    pub fn world2screen(&self, p: Vec2) -> Vec2 {
+        (p - self.pos()) * self.zoom * vec2(1.0, -1.0) + self.window_size / 2.0
+    }
 }
@@ -10,6 +10,7 @@ uniform Camera {
 layout (location=0) in vec4 v_color;
 layout (location=1) in vec2 v_pos;
 layout (location=2) in float v_size;
+layout (location=3) in float v_angle;
 
 layout (location=0) out vec4 out_color;
 
@@ -18,5 +19,12 @@ void main() {
     vec2 vcoord = (vec2(v_pos.x, -v_pos.y) * 0.5) + 0.5;
     vec2 pdiff = 2.0 * ((pcoord - vcoord) / (vec2(v_size) / u_window_size));
     float alpha = clamp(1.0 - dot(pdiff, pdiff), 0.0, 1.0 / v_size) * v_size;
-    out_color = vec4(vec3(v_color.rgb), alpha);
+
+    // Orientation marker, a line from the centre to the edge. Fragment y points down
+    vec2 dir = vec2(cos(v_angle), -sin(v_angle));
+    float along = dot(pdiff, dir);
+    float across = abs(pdiff.x * dir.y - pdiff.y * dir.x) * 0.5 * v_size;
+    float marker = (v_size >= 6.0 && along > 0.0 && across < 0.75) ? 0.6 : 0.0;
+
+    out_color = vec4(v_color.rgb * (1.0 - marker), alpha);
 }
\ No newline at end of file
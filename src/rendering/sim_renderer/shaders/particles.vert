@@ -3,10 +3,12 @@
 layout (location=0) in vec2 a_pos;
 layout (location=1) in vec4 a_color;
 layout (location=2) in float a_size;
+layout (location=3) in float a_angle;
 
 layout (location=0) out vec4 v_color;
 layout (location=1) out vec2 v_pos;
 layout (location=2) out float v_size;
+layout (location=3) out float v_angle;
 
 layout (std140, binding=0)
 uniform Camera {
@@ -22,6 +24,7 @@ void main() {
     v_color = a_color;
     v_pos   = u_zoom * (trans + pos);
     v_size  = max(a_size * u_zoom.x, 1.0) * 2.0;
+    v_angle = a_angle;
     
     gl_Position = vec4(v_pos, 0, 1);
     gl_PointSize = v_size;
@@ -2,12 +2,12 @@ use bytemuck;
 use glam::vec2;
 use shaderc::CompileOptions;
 use wgpu::{ShaderModuleDescriptor, TextureView};
-use winit::event::VirtualKeyCode;
+use winit::event::{ElementState, VirtualKeyCode};
 
 use super::{camera::Camera, Vertex, VertexBuffer};
 use crate::{
     rendering::{Display, PetriEventHandler},
-    simulation::Simulation,
+    simulation::{Simulation, Time},
     timing::timer::time_func,
 };
 
@@ -116,7 +116,7 @@ impl SimRenderer {
                 buffers: &[wgpu::VertexBufferLayout {
                     array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
                     step_mode: wgpu::VertexStepMode::Vertex,
-                    attributes: &wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x4, 2 => Float32],
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x4, 2 => Float32, 3 => Float32],
                 }],
             },
             fragment: Some(wgpu::FragmentState {
@@ -224,7 +224,7 @@ impl PetriEventHandler for SimRenderer {
     fn handle_keyboard_input(
         &mut self,
         display: &mut Display,
-        _simulation: &mut Simulation,
+        simulation: &mut Simulation,
         input: &winit::event::KeyboardInput,
     ) {
         if input.virtual_keycode.is_some() {
@@ -236,5 +236,23 @@ impl PetriEventHandler for SimRenderer {
                 _ => {}
             }
         }
+
+        // Simulation clock controls only fire once per key press
+        if input.state != ElementState::Pressed {
+            return;
+        }
+        if let Some(key) = input.virtual_keycode {
+            let time_scale = simulation.resources.get::<Time>().unwrap().time_scale;
+            match key {
+                VirtualKeyCode::Space => simulation.toggle_pause(),
+                VirtualKeyCode::Period => simulation.step(1),
+                VirtualKeyCode::Comma => {
+                    simulation.step_back();
+                }
+                VirtualKeyCode::LBracket => simulation.set_time_scale(time_scale * 0.5),
+                VirtualKeyCode::RBracket => simulation.set_time_scale(time_scale * 2.0),
+                _ => {}
+            }
+        }
     }
 }
@@ -12,6 +12,7 @@ pub struct Vertex {
     position: [f32; 2],
     color: [f32; 4],
     radius: f32,
+    angle: f32,
 }
 
 impl Vertex {
@@ -20,6 +21,7 @@ impl Vertex {
             position: circ.pos.into(),
             color: *color,
             radius: circ.radius,
+            angle: circ.angle,
         }
     }
 }
@@ -1,7 +1,7 @@
 use glam::{vec2, Vec2};
 use rand::prelude::*;
 
-use super::Config;
+use super::{Config, MAX_TYPES};
 
 #[derive(Clone, Copy)]
 pub struct RigidCircle {
@@ -10,6 +10,13 @@ pub struct RigidCircle {
     pub radius: f32,
     pub to_pos: Vec2,
     pub to_vel: Vec2,
+    /// Orientation in radians, counter clockwise
+    pub angle: f32,
+    /// Spin in radians per second, counter clockwise
+    pub angular_vel: f32,
+    pub to_angular_vel: f32,
+    /// Particle type, indexes the interaction rules
+    pub kind: u8,
 }
 
 #[derive(Clone, Copy)]
@@ -18,35 +25,58 @@ pub struct Color {
 }
 
 impl RigidCircle {
-    pub fn new_rand(config: &Config) -> Self {
-        let bounds = config.bounds;
+    pub fn new_rand<R: Rng>(config: &Config, rng: &mut R) -> Self {
+        Self::new_rand_in(config, config.bounds, rng)
+    }
+
+    /// Random circle of a random type with a position inside `region`, spanned by any two
+    /// opposite corners. The region must not be empty
+    pub fn new_rand_in<R: Rng>(config: &Config, region: (Vec2, Vec2), rng: &mut R) -> Self {
+        let (min, max) = (region.0.min(region.1), region.0.max(region.1));
+        let pos = vec2(rng.gen_range(min.x..max.x), rng.gen_range(min.y..max.y));
 
-        let pos = vec2(
-            thread_rng().gen_range(bounds.0.x..bounds.1.x),
-            thread_rng().gen_range(bounds.0.y..bounds.1.y),
-        );
+        let vel = vec2(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0));
 
-        let vel = vec2(thread_rng().gen_range(-1.0..1.0), thread_rng().gen_range(-1.0..1.0));
+        let radius = match config.max_cell_radius > config.cell_radius {
+            true => (rng.gen_range(config.cell_radius.ln()..config.max_cell_radius.ln())).exp(),
+            false => config.cell_radius,
+        };
 
         Self {
             pos,
             to_pos: pos,
             vel,
             to_vel: vel,
-            radius: config.cell_radius,
+            angle: rng.gen_range(0.0..std::f32::consts::TAU),
+            angular_vel: 0.0,
+            to_angular_vel: 0.0,
+            radius,
+            kind: rng.gen_range(0..config.rules.n_types.clamp(1, MAX_TYPES)) as u8,
         }
     }
 }
 
 impl Color {
-    pub fn new_rand() -> Self {
+    pub fn new_rand<R: Rng>(rng: &mut R) -> Self {
+        Self {
+            val: [rng.gen_range(0.0..1.0), rng.gen_range(0.0..1.0), rng.gen_range(0.0..1.0), 1.0],
+        }
+    }
+
+    /// Random colors for a single type, otherwise one evenly spaced hue per type
+    pub fn for_kind<R: Rng>(kind: u8, n_types: usize, rng: &mut R) -> Self {
+        if n_types <= 1 {
+            return Self::new_rand(rng);
+        }
+
+        let hue = kind as f32 / n_types as f32 * 6.0;
+        let channel = |offset: f32| {
+            let k = (offset + hue) % 6.0;
+            1.0 - k.min(4.0 - k).clamp(0.0, 1.0)
+        };
+
         Self {
-            val: [
-                thread_rng().gen_range(0.0..1.0),
-                thread_rng().gen_range(0.0..1.0),
-                thread_rng().gen_range(0.0..1.0),
-                1.0,
-            ],
+            val: [channel(5.0), channel(3.0), channel(1.0), 1.0],
         }
     }
 }
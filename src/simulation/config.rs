@@ -1,18 +1,112 @@
 use glam::{vec2, Vec2};
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy)]
+use super::{Contact, Fields, ForceCurves, LongRange, Potentials, Rules, SpatialIndexKind, Thermostat, Timestep};
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct Config {
     pub n_cells: u32,
     pub cell_radius: f32,
+    /// When above `cell_radius`, radii are spread log-uniformly between the two
+    pub max_cell_radius: f32,
     pub bounds: (Vec2, Vec2),
+    /// Let particles leave `bounds` instead of bouncing off its walls.
+    /// `bounds` then only decides where particles spawn, use an unbounded `spatial_index`
+    pub open_bounds: bool,
+    /// Broadphase used for neighbour queries
+    pub spatial_index: SpatialIndexKind,
+    /// Side length of a spatial grid cell, must be a power of two.
+    /// 0 derives it from the collision and interaction distances
+    pub grid_cell_size: u32,
+    /// Time steps at a few cell sizes around `grid_cell_size` every so often and keep the fastest
+    pub auto_tune_grid: bool,
+    /// Extra distance Verlet neighbour lists reach past the interaction range. Lists are reused
+    /// until a particle moves more than half of it, 0 queries `spatial_index` every tick instead
+    pub verlet_skin: f32,
+    /// Fixed or adaptive dt
+    pub timestep: Timestep,
+    /// Physics steps per tick, each covering an equal share of its dt. More are slower but
+    /// resolve fast collisions and stiff forces more accurately
+    pub substeps: u32,
+    /// Passes over the contacts in each substep, instead of `contact.iterations`. Short substeps
+    /// often need fewer
+    pub substep_contact_iterations: Option<u32>,
+    /// Ticks between sorting particle storage along a Z-order curve for cache locality, 0 disables
+    pub reorder_interval: u32,
+    /// Restitution and friction of collisions
+    pub contact: Contact,
+    /// Particle types and how they interact
+    pub rules: Rules,
+    /// Hand drawn forces between type pairs, added to the rules
+    pub force_curves: ForceCurves,
+    /// Lennard-Jones and similar forces between nearby particles
+    pub potentials: Potentials,
+    /// Gravity or electrostatics between every pair of particles
+    pub long_range: LongRange,
+    /// Gravity, attractors, vortices and flows imposed from outside
+    pub fields: Fields,
+    /// Holds the particles at a target temperature
+    pub thermostat: Thermostat,
+    /// Seed for every random choice made by the simulation
+    pub seed: u64,
+
+    /// Ticks between rewind snapshots, 0 disables rewinding
+    pub history_interval: u32,
+    /// Maximum number of rewind snapshots kept
+    pub history_len: usize,
+    /// Memory budget for rewind snapshots in MiB
+    pub history_max_mb: usize,
 }
 
-impl Config {
-    pub fn default() -> Config {
+impl Default for Config {
+    fn default() -> Config {
         Config {
             n_cells: 100_000,
             cell_radius: 2.0,
+            max_cell_radius: 0.0,
             bounds: (vec2(0.0, 0.0), vec2(8192.0, 8192.0)),
+            open_bounds: false,
+            spatial_index: SpatialIndexKind::DenseGrid,
+            grid_cell_size: 0,
+            auto_tune_grid: false,
+            verlet_skin: 0.0,
+            timestep: Timestep::default(),
+            substeps: 1,
+            substep_contact_iterations: None,
+            reorder_interval: 0,
+            contact: Contact::default(),
+            rules: Rules::default(),
+            force_curves: ForceCurves::default(),
+            potentials: Potentials::default(),
+            long_range: LongRange::default(),
+            fields: Fields::default(),
+            thermostat: Thermostat::default(),
+            seed: 0,
+            history_interval: 0,
+            history_len: 300,
+            history_max_mb: 512,
+        }
+    }
+}
+
+impl Config {
+    /// Largest radius a particle can spawn with
+    pub fn max_radius(&self) -> f32 {
+        self.cell_radius.max(self.max_cell_radius)
+    }
+
+    /// Farthest apart a particle of `radius` can be from one of the same size and still interact,
+    /// by touching or through the rules, force curves and potentials
+    pub fn reach(&self, radius: f32) -> f32 {
+        let mut reach = 2.0 * radius;
+        if !self.rules.is_inert() {
+            reach = reach.max(self.rules.interaction_radius);
+        }
+        reach = reach.max(self.force_curves.range());
+        if !self.potentials.is_inert() {
+            reach = reach.max(self.potentials.range());
         }
+        reach
     }
 }
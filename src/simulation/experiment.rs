@@ -0,0 +1,144 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use glam::Vec2;
+use legion::*;
+use serde::{Deserialize, Serialize};
+
+use super::{Config, RigidCircle, Rules};
+
+/// A reproducible run: the starting config plus a timeline of events keyed by tick.
+///
+/// Loaded from JSON, e.g.
+/// ```json
+/// {
+///     "name": "inject",
+///     "config": { "n_cells": 20000, "seed": 7 },
+///     "events": [
+///         { "tick": 5000, "type": "inject", "count": 5000, "min": [0, 0], "max": [1024, 1024] },
+///         { "tick": 0, "every": 1000, "type": "export_snapshot", "path": "out/{tick}.csv" }
+///     ]
+/// }
+/// ```
+/// Fields missing from `config` take their default values, unknown ones are an error.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Experiment {
+    pub name: String,
+    pub config: Config,
+    pub events: Vec<ScheduledEvent>,
+}
+
+impl Experiment {
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let experiment: Experiment = serde_json::from_reader(io::BufReader::new(file))?;
+        experiment
+            .validate()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        Ok(experiment)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let file = File::create(path)?;
+        Ok(serde_json::to_writer_pretty(BufWriter::new(file), self)?)
+    }
+
+    /// Rejects events that would fail when they fire
+    fn validate(&self) -> Result<(), String> {
+        for scheduled in self.events.iter() {
+            if let Event::Inject { min, max, .. } = scheduled.event {
+                let size = (max - min).abs();
+                if size.is_nan() || size.min_element() <= 0.0 {
+                    return Err(format!("inject at tick {} has an empty region", scheduled.tick));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ScheduledEvent {
+    /// First tick the event fires at, after that tick has been simulated
+    pub tick: u64,
+    /// Repeat period in ticks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub every: Option<u64>,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+impl ScheduledEvent {
+    pub fn fires_at(&self, tick: u128) -> bool {
+        let start = self.tick as u128;
+        match self.every {
+            Some(period) if period > 0 => tick >= start && (tick - start).is_multiple_of(period as u128),
+            _ => tick == start,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    Pause,
+    SetTimeScale { scale: f32 },
+    /// `null` runs as fast as possible
+    SetTargetTickRate { rate: Option<u32> },
+    /// Replaces the interaction rules, unspecified fields take their defaults. Boxed, the type
+    /// matrix would make every event as large
+    SetRules { rules: Box<Rules> },
+    /// Spawns `count` particles uniformly inside the region spanned by corners `min` and `max`
+    Inject { count: u32, min: Vec2, max: Vec2 },
+    /// Writes every particle to a CSV file, `{tick}` in the path is replaced by the current tick
+    ExportSnapshot { path: String },
+}
+
+/// Ordered set of events executed by `Simulation::update`
+#[derive(Clone, Default)]
+pub struct Timeline {
+    events: Vec<ScheduledEvent>,
+}
+
+impl Timeline {
+    pub fn new(events: Vec<ScheduledEvent>) -> Self {
+        Self { events }
+    }
+
+    pub fn due(&self, tick: u128) -> impl Iterator<Item = &Event> {
+        self.events
+            .iter()
+            .filter(move |scheduled| scheduled.fires_at(tick))
+            .map(|scheduled| &scheduled.event)
+    }
+}
+
+/// Writes position, velocity, radius, type, orientation and spin of every particle as CSV
+pub fn export_csv<P: AsRef<Path>>(world: &World, path: P) -> io::Result<()> {
+    if let Some(dir) = path.as_ref().parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(out, "x,y,vx,vy,radius,kind,angle,spin")?;
+    for circ in <&RigidCircle>::query().iter(world) {
+        writeln!(
+            out,
+            "{},{},{},{},{},{},{},{}",
+            circ.pos.x,
+            circ.pos.y,
+            circ.vel.x,
+            circ.vel.y,
+            circ.radius,
+            circ.kind,
+            circ.angle,
+            circ.angular_vel
+        )?;
+    }
+
+    out.flush()
+}
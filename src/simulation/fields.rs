@@ -0,0 +1,216 @@
+use std::convert::TryFrom;
+
+use glam::{vec2, Vec2};
+use serde::{Deserialize, Serialize};
+
+/// Most external fields that can act at once
+pub const MAX_FIELDS: usize = 8;
+/// Step in noise space the flow field's curl is taken over
+const CURL_STEP: f32 = 0.01;
+
+/// Shape of an external field
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldKind {
+    /// Constant pull along `direction`
+    Gravity,
+    /// Pulls particles towards its position, a negative strength repels them
+    Attractor,
+    /// Swirls particles counter clockwise around its position
+    Vortex,
+    /// Pushes along `direction`, more strongly the further across it a particle is, and the
+    /// other way on the far side
+    Shear,
+    /// Swirling flow from the curl of smooth noise, so it neither gathers nor scatters particles
+    Noise,
+}
+
+/// How a field weakens with distance from its position, in units of its `radius`
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Falloff {
+    /// Same strength everywhere
+    None,
+    /// Fades to nothing at `radius`
+    Linear,
+    /// Halved at `radius`, then dropping with the square of the distance
+    InverseSquare,
+    /// Bell shaped, down to about a third at `radius` and all but gone by three radii
+    Gaussian,
+}
+
+/// An external field accelerating every particle it reaches, whatever its size
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Field {
+    pub kind: FieldKind,
+    pub pos: Vec2,
+    /// Acceleration at full strength. `Shear` reaches it one radius across the field
+    pub strength: f32,
+    pub falloff: Falloff,
+    /// Length scale of the field, used by the falloff and as the size of the noise's swirls.
+    /// Must be positive
+    pub radius: f32,
+    /// Which way `Gravity` pulls and `Shear` pushes
+    pub direction: Vec2,
+}
+
+impl Default for Field {
+    fn default() -> Self {
+        Self {
+            kind: FieldKind::Gravity,
+            pos: Vec2::ZERO,
+            strength: 0.05,
+            falloff: Falloff::None,
+            radius: 256.0,
+            direction: vec2(0.0, -1.0),
+        }
+    }
+}
+
+impl Field {
+    /// Acceleration of a particle at `pos`. `seed` picks the noise
+    #[inline]
+    pub fn accel(&self, pos: Vec2, seed: u64) -> Vec2 {
+        let del = pos - self.pos;
+        let dist = del.length();
+        let direction = self.direction.normalize_or_zero();
+
+        let accel = match self.kind {
+            FieldKind::Gravity => direction,
+            FieldKind::Attractor if dist > 0.0 => -del / dist,
+            FieldKind::Vortex if dist > 0.0 => del.perp() / dist,
+            FieldKind::Attractor | FieldKind::Vortex => Vec2::ZERO,
+            FieldKind::Shear => direction * del.dot(direction.perp()) / self.radius,
+            FieldKind::Noise => curl_noise(del / self.radius, seed),
+        };
+        accel * self.strength * self.weight(dist)
+    }
+
+    /// Share of the strength left `dist` from the field's position
+    #[inline]
+    fn weight(&self, dist: f32) -> f32 {
+        let dist = dist / self.radius;
+        match self.falloff {
+            Falloff::None => 1.0,
+            Falloff::Linear => (1.0 - dist).max(0.0),
+            Falloff::InverseSquare => 1.0 / (1.0 + dist * dist),
+            Falloff::Gaussian => (-dist * dist).exp(),
+        }
+    }
+}
+
+/// External fields acting on the particles, their accelerations add up
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(try_from = "Vec<Field>", into = "Vec<Field>")]
+pub struct Fields {
+    /// Only the first `len` are used
+    fields: [Field; MAX_FIELDS],
+    len: usize,
+}
+
+impl Default for Fields {
+    fn default() -> Self {
+        Self {
+            fields: [Field::default(); MAX_FIELDS],
+            len: 0,
+        }
+    }
+}
+
+impl TryFrom<Vec<Field>> for Fields {
+    type Error = String;
+
+    fn try_from(def: Vec<Field>) -> Result<Self, Self::Error> {
+        if def.len() > MAX_FIELDS {
+            return Err(format!("{} fields given, at most {} are allowed", def.len(), MAX_FIELDS));
+        }
+
+        let mut fields = Fields::default();
+        for field in def {
+            if field.radius.is_nan() || field.radius <= 0.0 {
+                return Err(format!("field radius must be positive, got {}", field.radius));
+            }
+            fields.push(field);
+        }
+        Ok(fields)
+    }
+}
+
+impl From<Fields> for Vec<Field> {
+    fn from(fields: Fields) -> Self {
+        fields.fields().to_vec()
+    }
+}
+
+impl Fields {
+    pub fn fields(&self) -> &[Field] {
+        &self.fields[..self.len]
+    }
+
+    pub fn fields_mut(&mut self) -> &mut [Field] {
+        &mut self.fields[..self.len]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Adds a field, returns its index or `None` if there is no room left
+    pub fn push(&mut self, field: Field) -> Option<usize> {
+        if self.len == MAX_FIELDS {
+            return None;
+        }
+        self.fields[self.len] = field;
+        self.len += 1;
+        Some(self.len - 1)
+    }
+
+    pub fn remove(&mut self, ind: usize) {
+        if ind < self.len {
+            self.fields.copy_within(ind + 1..self.len, ind);
+            self.len -= 1;
+        }
+    }
+
+    /// Summed acceleration of every field at `pos`
+    #[inline]
+    pub fn accel(&self, pos: Vec2, seed: u64) -> Vec2 {
+        self.fields().iter().fold(Vec2::ZERO, |sum, field| sum + field.accel(pos, seed))
+    }
+}
+
+/// Curl of smooth noise at `p`, measured in noise cells. Flows along the noise's contour lines
+fn curl_noise(p: Vec2, seed: u64) -> Vec2 {
+    let (dx, dy) = (vec2(CURL_STEP, 0.0), vec2(0.0, CURL_STEP));
+    let ddx = value_noise(p + dx, seed) - value_noise(p - dx, seed);
+    let ddy = value_noise(p + dy, seed) - value_noise(p - dy, seed);
+    vec2(ddy, -ddx) / (2.0 * CURL_STEP)
+}
+
+/// Noise between -1 and 1 interpolated smoothly between random values at whole coordinates
+fn value_noise(p: Vec2, seed: u64) -> f32 {
+    let cell = p.floor();
+    let (x, y) = (cell.x as i32, cell.y as i32);
+    let t = p - cell;
+    // Quintic smoothstep, its slope and curvature vanish at the corners so the curl is continuous
+    let t = t * t * t * (t * (t * 6.0 - Vec2::splat(15.0)) + Vec2::splat(10.0));
+
+    let bottom = lerp(lattice(x, y, seed), lattice(x + 1, y, seed), t.x);
+    let top = lerp(lattice(x, y + 1, seed), lattice(x + 1, y + 1, seed), t.x);
+    lerp(bottom, top, t.y)
+}
+
+/// Random value between -1 and 1 fixed to a whole coordinate
+fn lattice(x: i32, y: i32, seed: u64) -> f32 {
+    let mut h = seed ^ (x as u32 as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15) ^ ((y as u32 as u64) << 32);
+    h = (h ^ (h >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    h = (h ^ (h >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    h ^= h >> 31;
+    (h >> 40) as f32 / (1u64 << 23) as f32 - 1.0
+}
+
+#[inline]
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
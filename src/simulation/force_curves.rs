@@ -0,0 +1,247 @@
+use std::convert::TryFrom;
+
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+use super::MAX_TYPES;
+
+/// Most control points a force curve can have
+pub const MAX_CURVE_POINTS: usize = 12;
+
+/// How a force curve is drawn between its control points
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Interpolation {
+    #[default]
+    Linear,
+    /// Cubic Hermite through every point, with slopes taken from the neighbouring points
+    Cubic,
+}
+
+/// Force against distance between two particle types, drawn through control points of
+/// `(distance, force)`. Positive forces attract like the rules do. Before the first point the
+/// force stays at its value, past the last one nothing acts
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(try_from = "CurveDef", into = "CurveDef")]
+pub struct ForceCurve {
+    /// Sorted by distance, only the first `len` are used
+    points: [Vec2; MAX_CURVE_POINTS],
+    len: usize,
+    pub interpolation: Interpolation,
+}
+
+/// Serialized form of a `ForceCurve`
+#[derive(Serialize, Deserialize)]
+struct CurveDef {
+    points: Vec<Vec2>,
+    #[serde(default)]
+    interpolation: Interpolation,
+}
+
+impl Default for ForceCurve {
+    fn default() -> Self {
+        Self {
+            points: [Vec2::ZERO; MAX_CURVE_POINTS],
+            len: 0,
+            interpolation: Interpolation::Linear,
+        }
+    }
+}
+
+impl TryFrom<CurveDef> for ForceCurve {
+    type Error = String;
+
+    fn try_from(def: CurveDef) -> Result<Self, Self::Error> {
+        if def.points.len() > MAX_CURVE_POINTS {
+            return Err(format!(
+                "force curve has {} points, at most {} are allowed",
+                def.points.len(),
+                MAX_CURVE_POINTS
+            ));
+        }
+
+        let mut curve = ForceCurve {
+            interpolation: def.interpolation,
+            ..Default::default()
+        };
+        for point in def.points {
+            curve.insert(point);
+        }
+        Ok(curve)
+    }
+}
+
+impl From<ForceCurve> for CurveDef {
+    fn from(curve: ForceCurve) -> Self {
+        Self {
+            points: curve.points().to_vec(),
+            interpolation: curve.interpolation,
+        }
+    }
+}
+
+impl ForceCurve {
+    pub fn points(&self) -> &[Vec2] {
+        &self.points[..self.len]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Distance past which the curve exerts no force
+    pub fn range(&self) -> f32 {
+        self.points().last().map_or(0.0, |last| last.x)
+    }
+
+    /// Adds a control point, distances below 0 are clamped. Returns its index, or `None` if the
+    /// curve is full
+    pub fn insert(&mut self, point: Vec2) -> Option<usize> {
+        if self.len == MAX_CURVE_POINTS {
+            return None;
+        }
+
+        let point = Vec2::new(point.x.max(0.0), point.y);
+        let ind = self.points().partition_point(|p| p.x <= point.x);
+        self.points.copy_within(ind..self.len, ind + 1);
+        self.points[ind] = point;
+        self.len += 1;
+        Some(ind)
+    }
+
+    pub fn remove(&mut self, ind: usize) {
+        if ind < self.len {
+            self.points.copy_within(ind + 1..self.len, ind);
+            self.len -= 1;
+        }
+    }
+
+    /// Moves control point `ind` to `point`, returns where it ends up once the points are re-sorted.
+    /// `None` if there is no point `ind`
+    pub fn move_point(&mut self, ind: usize, point: Vec2) -> Option<usize> {
+        if ind >= self.len {
+            return None;
+        }
+        self.remove(ind);
+        self.insert(point)
+    }
+
+    /// Force between particles `dist` apart
+    #[inline]
+    pub fn eval(&self, dist: f32) -> f32 {
+        let points = self.points();
+        let (first, last) = match (points.first(), points.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return 0.0,
+        };
+        if dist >= last.x {
+            return 0.0;
+        }
+        if dist <= first.x {
+            return first.y;
+        }
+
+        // points[k].x <= dist < points[k + 1].x
+        let k = points.partition_point(|p| p.x <= dist) - 1;
+        let (a, b) = (points[k], points[k + 1]);
+        let h = b.x - a.x;
+        let t = (dist - a.x) / h;
+
+        match self.interpolation {
+            Interpolation::Linear => a.y + (b.y - a.y) * t,
+            Interpolation::Cubic => {
+                let (t2, t3) = (t * t, t * t * t);
+                a.y * (2.0 * t3 - 3.0 * t2 + 1.0)
+                    + h * self.slope(k) * (t3 - 2.0 * t2 + t)
+                    + b.y * (3.0 * t2 - 2.0 * t3)
+                    + h * self.slope(k + 1) * (t3 - t2)
+            }
+        }
+    }
+
+    /// Slope of the cubic at point `k`, from its neighbours on either side
+    fn slope(&self, k: usize) -> f32 {
+        let points = self.points();
+        let (l, r) = (points[k.saturating_sub(1)], points[(k + 1).min(self.len - 1)]);
+        match r.x > l.x {
+            true => (r.y - l.y) / (r.x - l.x),
+            false => 0.0,
+        }
+    }
+}
+
+/// Hand drawn forces between every ordered pair of particle types, on top of the rules.
+/// Serialized as a list of the curves in use, e.g.
+/// `[{ "from": 0, "to": 1, "points": [[0, -1], [8, 0.5], [24, 0]], "interpolation": "cubic" }]`
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(try_from = "Vec<PairCurve>", into = "Vec<PairCurve>")]
+pub struct ForceCurves {
+    /// `curves[i][j]` is the force on a particle of type `i` from one of type `j`
+    pub curves: [[ForceCurve; MAX_TYPES]; MAX_TYPES],
+}
+
+/// Serialized form of one of the `ForceCurves`
+#[derive(Serialize, Deserialize)]
+struct PairCurve {
+    from: u8,
+    to: u8,
+    #[serde(flatten)]
+    curve: ForceCurve,
+}
+
+impl TryFrom<Vec<PairCurve>> for ForceCurves {
+    type Error = String;
+
+    fn try_from(pairs: Vec<PairCurve>) -> Result<Self, Self::Error> {
+        let mut curves = ForceCurves::default();
+        for PairCurve { from, to, curve } in pairs {
+            if from as usize >= MAX_TYPES || to as usize >= MAX_TYPES {
+                return Err(format!("force curve from type {} to type {} is out of range", from, to));
+            }
+            curves.curves[from as usize][to as usize] = curve;
+        }
+        Ok(curves)
+    }
+}
+
+impl From<ForceCurves> for Vec<PairCurve> {
+    fn from(curves: ForceCurves) -> Self {
+        let mut pairs = Vec::new();
+        for (from, row) in curves.curves.iter().enumerate() {
+            for (to, curve) in row.iter().enumerate().filter(|(_, curve)| !curve.is_empty()) {
+                pairs.push(PairCurve {
+                    from: from as u8,
+                    to: to as u8,
+                    curve: *curve,
+                });
+            }
+        }
+        pairs
+    }
+}
+
+impl ForceCurves {
+    /// True when every curve is empty and the curves can be skipped
+    pub fn is_inert(&self) -> bool {
+        self.curves.iter().flatten().all(|curve| curve.is_empty())
+    }
+
+    /// Longest distance any curve reaches
+    pub fn range(&self) -> f32 {
+        self.curves
+            .iter()
+            .flatten()
+            .fold(0.0, |range, curve| range.max(curve.range()))
+    }
+
+    /// Force on a particle of type `from` exerted by one of type `to` offset by `del`
+    #[inline]
+    pub fn force(&self, from: u8, to: u8, del: Vec2) -> Vec2 {
+        let curve = &self.curves[from as usize][to as usize];
+        let dist = del.length();
+        if dist <= 0.0 || curve.is_empty() {
+            return Vec2::ZERO;
+        }
+        del / dist * curve.eval(dist)
+    }
+}
@@ -0,0 +1,257 @@
+use std::{
+    collections::VecDeque,
+    io::{Read, Write},
+    mem,
+};
+
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use glam::vec2;
+use legion::*;
+use rand::rngs::StdRng;
+use rayon::prelude::*;
+
+use super::{physics::Carryover, Color, Config, RigidCircle, Time};
+use crate::timing::timer::time_func;
+
+/// Number of per particle columns stored in a snapshot
+const N_COLUMNS: usize = 12;
+
+/// Compressed copy of every particle at a given tick, along with everything else the following
+/// ticks depend on, so running on from a restored snapshot repeats the original run.
+///
+/// Each particle field is stored as its own column, byte shuffled so the high bytes of
+/// neighbouring floats line up, then deflated.
+pub struct Snapshot {
+    pub tick: u128,
+    pub sim_time: f64,
+    pub n_particles: usize,
+    /// Entity of each particle, in column order
+    entities: Vec<Entity>,
+    columns: Vec<Vec<u8>>,
+    /// Time step of the tick the snapshot was taken after
+    dt: f32,
+    time_scale: f32,
+    /// Config as events and the viewer had left it
+    pub config: Config,
+    /// State of the simulation's random number generator, which inject events draw from
+    pub rng: StdRng,
+    /// What the physics pipeline hands from this tick to the next
+    pub carryover: Carryover,
+}
+
+impl Snapshot {
+    pub fn capture(world: &World, time: &Time, config: &Config, rng: &StdRng, carryover: Carryover) -> Self {
+        let n = world.len();
+        let mut raw: Vec<Vec<u32>> = (0..N_COLUMNS).map(|_| Vec::with_capacity(n)).collect();
+        let mut entities = Vec::with_capacity(n);
+
+        <(Entity, &RigidCircle, &Color)>::query().for_each(world, |(entity, circ, color)| {
+            entities.push(*entity);
+            // The next tick is computed entirely from to_pos / to_vel / to_angular_vel and the angle
+            raw[0].push(circ.to_pos.x.to_bits());
+            raw[1].push(circ.to_pos.y.to_bits());
+            raw[2].push(circ.to_vel.x.to_bits());
+            raw[3].push(circ.to_vel.y.to_bits());
+            raw[4].push(circ.radius.to_bits());
+            raw[5].push(circ.kind as u32);
+            raw[6].push(circ.angle.to_bits());
+            raw[7].push(circ.to_angular_vel.to_bits());
+            for (col, channel) in raw[8..].iter_mut().zip(color.val) {
+                col.push(channel.to_bits());
+            }
+        });
+
+        let columns = raw.par_iter().map(|col| compress(col)).collect();
+
+        Self {
+            tick: time.tick,
+            sim_time: time.sim_time,
+            n_particles: entities.len(),
+            entities,
+            columns,
+            dt: time.dt(),
+            time_scale: time.time_scale,
+            config: *config,
+            rng: rng.clone(),
+            carryover,
+        }
+    }
+
+    /// Decompresses the snapshot back into components
+    pub fn particles(&self) -> Vec<(RigidCircle, Color)> {
+        let raw: Vec<Vec<u32>> = self
+            .columns
+            .par_iter()
+            .map(|col| decompress(col, self.n_particles))
+            .collect();
+
+        (0..self.n_particles)
+            .map(|i| {
+                let pos = vec2(f32::from_bits(raw[0][i]), f32::from_bits(raw[1][i]));
+                let vel = vec2(f32::from_bits(raw[2][i]), f32::from_bits(raw[3][i]));
+                let angular_vel = f32::from_bits(raw[7][i]);
+                let circ = RigidCircle {
+                    pos,
+                    vel,
+                    radius: f32::from_bits(raw[4][i]),
+                    to_pos: pos,
+                    to_vel: vel,
+                    angle: f32::from_bits(raw[6][i]),
+                    angular_vel,
+                    to_angular_vel: angular_vel,
+                    kind: raw[5][i] as u8,
+                };
+                let color = Color {
+                    val: [8, 9, 10, 11].map(|col| f32::from_bits(raw[col][i])),
+                };
+                (circ, color)
+            })
+            .collect()
+    }
+
+    /// Puts the world, clock and config back as they were. Particles keep their entities and any
+    /// other components, those spawned since are removed. Only if the world no longer holds every
+    /// particle of the snapshot, in order, is it rebuilt with new entities
+    pub fn restore(&self, world: &mut World, time: &mut Time, config: &mut Config) {
+        let particles = self.particles();
+        let current: Vec<Entity> = <(Entity, &RigidCircle, &Color)>::query()
+            .iter(world)
+            .map(|(entity, ..)| *entity)
+            .collect();
+
+        if current.starts_with(&self.entities) {
+            // Spawned particles sit at the end of their storage, so removing them last first
+            // leaves the rest in place and in order
+            for entity in current[self.entities.len()..].iter().rev() {
+                world.remove(*entity);
+            }
+            <(&mut RigidCircle, &mut Color)>::query()
+                .iter_mut(world)
+                .zip(particles)
+                .for_each(|((circ, color), (restored_circ, restored_color))| {
+                    *circ = restored_circ;
+                    *color = restored_color;
+                });
+        } else {
+            world.clear();
+            world.extend(particles);
+        }
+
+        time.rewind(self.tick, self.sim_time, self.dt);
+        time.time_scale = self.time_scale;
+        *config = self.config;
+    }
+
+    /// Size in bytes, compressed columns and everything else
+    pub fn size(&self) -> usize {
+        self.columns.iter().map(Vec::len).sum::<usize>()
+            + self.entities.len() * mem::size_of::<Entity>()
+            + self.carryover.size()
+            + mem::size_of::<Self>()
+    }
+}
+
+/// Ring buffer of recent snapshots, bounded by both count and total compressed size
+pub struct History {
+    /// Ticks between snapshots, 0 disables recording
+    pub interval: u32,
+    pub capacity: usize,
+    pub max_bytes: usize,
+
+    snapshots: VecDeque<Snapshot>,
+    bytes: usize,
+}
+
+impl History {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            interval: config.history_interval,
+            capacity: config.history_len,
+            max_bytes: config.history_max_mb * 1024 * 1024,
+            snapshots: VecDeque::with_capacity(config.history_len),
+            bytes: 0,
+        }
+    }
+
+    /// Captures a snapshot if the current tick falls on the recording interval. `carryover` is
+    /// only called then
+    pub fn record(
+        &mut self,
+        world: &World,
+        time: &Time,
+        config: &Config,
+        rng: &StdRng,
+        carryover: impl FnOnce() -> Carryover,
+    ) {
+        if self.interval == 0 || self.capacity == 0 || !time.tick.is_multiple_of(self.interval as u128) {
+            return;
+        }
+        time_func!(history, capture);
+
+        // Recording after a rewind branches the timeline, so anything as new or newer is stale
+        self.truncate_from(time.tick);
+
+        let snapshot = Snapshot::capture(world, time, config, rng, carryover());
+        self.bytes += snapshot.size();
+        self.snapshots.push_back(snapshot);
+
+        while self.snapshots.len() > self.capacity || (self.bytes > self.max_bytes && self.snapshots.len() > 1) {
+            let oldest = self.snapshots.pop_front().unwrap();
+            self.bytes -= oldest.size();
+        }
+    }
+
+    /// Latest snapshot taken at or before `tick`
+    pub fn at_or_before(&self, tick: u128) -> Option<&Snapshot> {
+        self.snapshots.iter().rev().find(|snap| snap.tick <= tick)
+    }
+
+    /// Drops every snapshot taken at or after `tick`
+    pub fn truncate_from(&mut self, tick: u128) {
+        while self.snapshots.back().is_some_and(|snap| snap.tick >= tick) {
+            let newest = self.snapshots.pop_back().unwrap();
+            self.bytes -= newest.size();
+        }
+    }
+
+    /// Range of ticks that can be rewound to
+    pub fn tick_range(&self) -> Option<(u128, u128)> {
+        Some((self.snapshots.front()?.tick, self.snapshots.back()?.tick))
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+    /// Total compressed size in bytes
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+}
+
+fn compress(col: &[u32]) -> Vec<u8> {
+    // Group byte k of every value together, deflate handles the result far better
+    let mut shuffled = vec![0u8; col.len() * 4];
+    for (i, val) in col.iter().enumerate() {
+        for (k, byte) in val.to_le_bytes().iter().enumerate() {
+            shuffled[k * col.len() + i] = *byte;
+        }
+    }
+
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::fast());
+    encoder.write_all(&shuffled).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn decompress(data: &[u8], n: usize) -> Vec<u32> {
+    let mut shuffled = Vec::with_capacity(n * 4);
+    DeflateDecoder::new(data).read_to_end(&mut shuffled).unwrap();
+
+    (0..n)
+        .map(|i| u32::from_le_bytes([shuffled[i], shuffled[n + i], shuffled[2 * n + i], shuffled[3 * n + i]]))
+        .collect()
+}
@@ -1,11 +1,26 @@
 mod components;
 mod config;
+mod experiment;
+mod fields;
+mod force_curves;
+mod history;
 mod physics;
+mod potentials;
+mod rules;
 pub mod simulation;
 mod time;
 
 pub use components::*;
 pub use config::*;
-pub use physics::PhysicsPipeline;
+pub use experiment::{Event, Experiment, ScheduledEvent, Timeline};
+pub use fields::*;
+pub use force_curves::*;
+pub use history::{History, Snapshot};
+pub use physics::{
+    Contact, LongRange, LongRangeKind, PhysicsPipeline, SpatialIndex, SpatialIndexKind, SpatialQuery, Thermostat,
+    ThermostatKind,
+};
+pub use potentials::*;
+pub use rules::*;
 pub use simulation::*;
-pub use time::Time;
+pub use time::{Time, Timestep};
@@ -0,0 +1,251 @@
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+use super::bounds_of;
+use crate::simulation::MAX_TYPES;
+
+/// Most particles a leaf holds before it is split
+const LEAF_SIZE: usize = 8;
+/// Deepest a node may sit, stops coincident particles from splitting forever
+const MAX_DEPTH: u32 = 24;
+/// Nodes waiting to be visited during a traversal, enough for `MAX_DEPTH` levels of 4 children
+const STACK_SIZE: usize = 4 * MAX_DEPTH as usize + 1;
+
+/// Which inverse square law the long range stage applies
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LongRangeKind {
+    Off,
+    /// Every particle attracts every other, mass goes with area
+    Gravity,
+    /// Particles carry the charge of their type, like charges repel
+    Electrostatic,
+}
+
+/// Forces between every pair of particles, summed approximately with a Barnes-Hut tree
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LongRange {
+    pub kind: LongRangeKind,
+    /// Gravitational or Coulomb constant
+    pub strength: f32,
+    /// Opening angle, a node is summed as a single body when its size over its distance is
+    /// below this. 0 is exact and quadratic, around 0.5 is typical
+    pub theta: f32,
+    /// Plummer softening length, keeps close encounters finite
+    pub softening: f32,
+    /// Charge of each particle type for `Electrostatic`
+    pub charges: [f32; MAX_TYPES],
+}
+
+impl Default for LongRange {
+    fn default() -> Self {
+        Self {
+            kind: LongRangeKind::Off,
+            strength: 1.0,
+            theta: 0.5,
+            softening: 4.0,
+            charges: [0.0; MAX_TYPES],
+        }
+    }
+}
+
+impl LongRange {
+    pub fn is_off(&self) -> bool {
+        self.kind == LongRangeKind::Off || self.strength == 0.0
+    }
+
+    /// What a particle contributes to the field, its mass or charge
+    #[inline]
+    pub fn source(&self, kind: u8, radius: f32) -> f32 {
+        match self.kind {
+            LongRangeKind::Off => 0.0,
+            LongRangeKind::Gravity => radius.powi(2),
+            LongRangeKind::Electrostatic => self.charges[kind as usize],
+        }
+    }
+
+    /// Scale turning the field at a particle into its acceleration
+    #[inline]
+    pub fn response(&self, kind: u8, radius: f32) -> f32 {
+        match self.kind {
+            LongRangeKind::Off => 0.0,
+            LongRangeKind::Gravity => self.strength,
+            LongRangeKind::Electrostatic => -self.strength * self.charges[kind as usize] / radius.powi(2),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Node {
+    /// Centre of the node's sources, weighted by their magnitude
+    centre: Vec2,
+    /// Summed source of every body under the node
+    source: f32,
+    /// Side length of the node's square
+    size: f32,
+    /// `nodes[first_child..first_child + n_children]` are the children, none for a leaf
+    first_child: u32,
+    n_children: u32,
+    /// `bodies[start..end]` are the bodies under the node
+    start: u32,
+    end: u32,
+}
+
+#[derive(Clone, Copy)]
+struct Body {
+    pos: Vec2,
+    source: f32,
+    id: u32,
+}
+
+/// Quadtree of point sources that sums the inverse square field of all of them at a point in
+/// O(log n), treating far away groups as one body at their centre. Signed sources such as
+/// charges are grouped around the centre of their magnitudes, so groups that mostly cancel
+/// out are approximated more coarsely
+#[derive(Default)]
+pub struct BarnesHut {
+    nodes: Vec<Node>,
+    /// Bodies ordered so each node's lie next to each other
+    bodies: Vec<Body>,
+}
+
+impl BarnesHut {
+    /// Rebuilds the tree over particles at `pos`, particle `i` contributing `source(i)`
+    pub fn build<F>(&mut self, pos: &[Vec2], source: F)
+    where
+        F: Fn(usize) -> f32,
+    {
+        self.nodes.clear();
+        self.bodies.clear();
+        self.bodies.extend(pos.iter().enumerate().map(|(i, pos)| Body {
+            pos: *pos,
+            source: source(i),
+            id: i as u32,
+        }));
+        if self.bodies.is_empty() {
+            return;
+        }
+
+        let (min, max) = bounds_of(pos.iter().copied());
+        let size = (max - min).max_element().max(f32::EPSILON);
+
+        self.nodes.push(self.leaf(min, size, 0, self.bodies.len()));
+        self.subdivide(0, min, 0);
+    }
+
+    /// Summed field of every body except `ignore` at `pos`, `theta` being the opening angle
+    pub fn field(&self, pos: Vec2, ignore: u32, theta: f32, softening: f32) -> Vec2 {
+        if self.nodes.is_empty() {
+            return Vec2::ZERO;
+        }
+
+        let (theta2, softening2) = (theta.powi(2), softening.powi(2));
+        let pull = |del: Vec2, source: f32| {
+            let dist2 = del.length_squared() + softening2;
+            del * (source / (dist2 * dist2.sqrt()))
+        };
+
+        let mut field = Vec2::ZERO;
+        let mut stack = [0u32; STACK_SIZE];
+        let mut len = 1;
+        while len > 0 {
+            len -= 1;
+            let node = &self.nodes[stack[len] as usize];
+
+            if node.n_children == 0 {
+                for body in &self.bodies[node.start as usize..node.end as usize] {
+                    if body.id != ignore {
+                        field += pull(body.pos - pos, body.source);
+                    }
+                }
+                continue;
+            }
+
+            let del = node.centre - pos;
+            if node.size.powi(2) < theta2 * del.length_squared() {
+                field += pull(del, node.source);
+            } else {
+                for child in node.first_child..node.first_child + node.n_children {
+                    stack[len] = child;
+                    len += 1;
+                }
+            }
+        }
+        field
+    }
+
+    /// Node over `bodies[start..end]` with its sources summed up
+    fn leaf(&self, min: Vec2, size: f32, start: usize, end: usize) -> Node {
+        let (source, weight, moment) = self.bodies[start..end].iter().fold(
+            (0.0, 0.0, Vec2::ZERO),
+            |(source, weight, moment), body| {
+                (source + body.source, weight + body.source.abs(), moment + body.pos * body.source.abs())
+            },
+        );
+        let centre = match weight > 0.0 {
+            true => moment / weight,
+            false => min + Vec2::splat(0.5 * size),
+        };
+
+        Node {
+            centre,
+            source,
+            size,
+            first_child: 0,
+            n_children: 0,
+            start: start as u32,
+            end: end as u32,
+        }
+    }
+
+    /// Splits node `ind`, whose square starts at `min`, into quadrants until the leaves are small
+    fn subdivide(&mut self, ind: usize, min: Vec2, depth: u32) {
+        let Node { size, start, end, .. } = self.nodes[ind];
+        let (start, end) = (start as usize, end as usize);
+        if end - start <= LEAF_SIZE || depth >= MAX_DEPTH {
+            return;
+        }
+
+        let half = 0.5 * size;
+        let mid = min + Vec2::splat(half);
+        let bodies = &mut self.bodies[start..end];
+        let split_y = partition(bodies, |body| body.pos.y < mid.y);
+        let split_x_low = partition(&mut bodies[..split_y], |body| body.pos.x < mid.x);
+        let split_x_high = split_y + partition(&mut bodies[split_y..], |body| body.pos.x < mid.x);
+
+        let quadrants = [
+            (min, 0, split_x_low),
+            (Vec2::new(mid.x, min.y), split_x_low, split_y),
+            (Vec2::new(min.x, mid.y), split_y, split_x_high),
+            (mid, split_x_high, end - start),
+        ];
+
+        let first_child = self.nodes.len();
+        for (corner, from, to) in quadrants.iter().filter(|(_, from, to)| to > from) {
+            let child = self.leaf(*corner, half, start + from, start + to);
+            self.nodes.push(child);
+        }
+        self.nodes[ind].first_child = first_child as u32;
+        self.nodes[ind].n_children = (self.nodes.len() - first_child) as u32;
+
+        for (child, (corner, ..)) in (first_child..).zip(quadrants.iter().filter(|(_, from, to)| to > from)) {
+            self.subdivide(child, *corner, depth + 1);
+        }
+    }
+}
+
+/// Moves the elements matching `pred` to the front, returns how many there are
+fn partition<T, F>(slice: &mut [T], pred: F) -> usize
+where
+    F: Fn(&T) -> bool,
+{
+    let mut split = 0;
+    for i in 0..slice.len() {
+        if pred(&slice[i]) {
+            slice.swap(split, i);
+            split += 1;
+        }
+    }
+    split
+}
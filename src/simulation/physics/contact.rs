@@ -0,0 +1,115 @@
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+use crate::simulation::MAX_TYPES;
+
+/// How touching particles bounce off and rub against each other
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Contact {
+    /// Fraction of the approach speed particles separate with, 1 is perfectly elastic
+    pub restitution: f32,
+    /// Per type overrides of `restitution`, a pair uses the mean of its two types
+    pub type_restitution: [Option<f32>; MAX_TYPES],
+    /// Coulomb friction coefficient, tangential impulses are at most this times the normal load
+    pub friction: f32,
+    /// Brakes the spin of particles rolling over each other in proportion to the normal load
+    pub rolling_resistance: f32,
+    /// Passes over every contact per tick. 1 resolves each contact once, all in parallel. More
+    /// run a sequential impulse solver that keeps crowded regions from compressing into overlap
+    pub iterations: u32,
+    /// Start the solver from the impulses each contact ended the last tick with
+    pub warm_starting: bool,
+    /// Particles moving more than this many radii in a tick are swept along their path so they
+    /// can't pass through others. 0 disables
+    pub ccd_threshold: f32,
+}
+
+impl Default for Contact {
+    fn default() -> Self {
+        Self {
+            restitution: 1.0,
+            type_restitution: [None; MAX_TYPES],
+            friction: 0.0,
+            rolling_resistance: 0.0,
+            iterations: 1,
+            warm_starting: true,
+            ccd_threshold: 1.0,
+        }
+    }
+}
+
+impl Contact {
+    /// Restitution between particles of types `a` and `b`
+    #[inline]
+    pub fn restitution(&self, a: u8, b: u8) -> f32 {
+        let of = |kind: u8| self.type_restitution[kind as usize].unwrap_or(self.restitution);
+        0.5 * (of(a) + of(b))
+    }
+
+    /// Response of circle `a` to touching circle `b` at offset `del`, with restitution
+    /// `restitution`. Friction acts where the surfaces meet, so it both drags and spins them.
+    /// Mass goes with area and the circles are uniform discs
+    #[inline]
+    pub fn resolve(&self, del: Vec2, a: Body, b: Body, restitution: f32, dt: f32) -> Response {
+        let dist = del.length();
+        let normal = del / dist;
+        let tangent = normal.perp();
+        let overlap = a.radius + b.radius - dist;
+        // Share of the exchange the first circle takes, 0.5 for equal sizes
+        let share = b.radius.powi(2) / (a.radius.powi(2) + b.radius.powi(2));
+
+        // Velocity of b's surface relative to a's where they touch
+        let vdel = b.vel - a.vel - tangent * (a.angular_vel * a.radius + b.angular_vel * b.radius);
+        let closing = -vdel.dot(normal);
+        let sliding = vdel.dot(tangent);
+
+        let mut response = Response {
+            pos: -normal * overlap * share,
+            ..Default::default()
+        };
+        // Resting contacts still press together, the overlap pushed out this tick stands in for the load
+        let mut load = share * overlap / dt;
+        if closing > 0.0 {
+            let impulse = (1.0 + restitution) * share * closing;
+            response.vel -= normal * impulse;
+            load += impulse;
+        }
+
+        if self.friction > 0.0 && sliding != 0.0 {
+            // A tangential impulse on a disc changes its surface speed three times as much as its
+            // centre's, two parts of it through the spin. Never more than it takes to stop sliding
+            let drag = (self.friction * load).min(share * sliding.abs() / 3.0) * sliding.signum();
+            response.vel += tangent * drag;
+            response.angular_vel += 2.0 * drag / a.radius;
+        }
+
+        let rolling = a.angular_vel - b.angular_vel;
+        if self.rolling_resistance > 0.0 && rolling != 0.0 {
+            // Torque of `rolling_resistance * load` at the reduced radius, never enough to reverse
+            // the spin. The share is by moment of inertia, which goes with radius^4
+            let reduced = a.radius * b.radius / (a.radius + b.radius);
+            let spin_share = b.radius.powi(4) / (a.radius.powi(4) + b.radius.powi(4));
+            let brake = 2.0 * self.rolling_resistance * reduced * load / a.radius.powi(2);
+            response.angular_vel -= brake.min(spin_share * rolling.abs()) * rolling.signum();
+        }
+
+        response
+    }
+}
+
+/// Motion of one side of a contact
+#[derive(Clone, Copy)]
+pub struct Body {
+    pub vel: Vec2,
+    pub angular_vel: f32,
+    pub radius: f32,
+}
+
+/// Change to a circle from one contact
+#[derive(Clone, Copy, Default)]
+pub struct Response {
+    pub pos: Vec2,
+    pub vel: Vec2,
+    pub angular_vel: f32,
+}
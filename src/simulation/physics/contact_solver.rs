@@ -0,0 +1,213 @@
+use fxhash::FxHashMap;
+use glam::Vec2;
+use legion::Entity;
+
+use super::{Contact, Particles};
+
+/// Pairs closer than this fraction of the smaller radius are handed to the solver before they
+/// touch, so pushing one contact apart can't drive a particle into a neighbour it doesn't know about
+pub const CONTACT_MARGIN: f32 = 0.5;
+/// Pairs closing by less than this fraction of the smaller radius a tick are resting and don't
+/// bounce. Otherwise the jostling in a packed region is reflected back as fresh energy every tick
+const BOUNCE_THRESHOLD: f32 = 0.05;
+
+/// Impulses a contact ended the last tick with
+#[derive(Clone, Copy, Default)]
+struct Impulses {
+    normal: f32,
+    tangent: f32,
+    rolling: f32,
+}
+
+/// A touching pair and what the solver needs to keep them apart
+struct Constraint {
+    a: usize,
+    b: usize,
+    /// Unit offset from `a` to `b`
+    normal: Vec2,
+    radius: (f32, f32),
+    inv_mass: (f32, f32),
+    inv_inertia: (f32, f32),
+    /// Inverse of how fast the relative velocity along the normal, along the tangent and the
+    /// relative spin react to a unit impulse
+    normal_mass: f32,
+    tangent_mass: f32,
+    rolling_mass: f32,
+    /// Least separating speed, negative for pairs that don't touch yet so they may close the gap
+    /// but not overshoot it
+    target: f32,
+    /// Speed the pair should bounce apart at once the rest is solved
+    bounce: f32,
+    friction: f32,
+    /// Most rolling torque per unit of normal impulse
+    rolling_limit: f32,
+    impulses: Impulses,
+}
+
+impl Constraint {
+    /// Velocity of b's surface relative to a's where they touch, along the normal and the tangent
+    fn relative_vel(&self, vel: &[Vec2], angular_vel: &[f32]) -> (f32, f32) {
+        let (a, b) = (self.a, self.b);
+        let vdel =
+            vel[b] - vel[a] - self.normal.perp() * (angular_vel[a] * self.radius.0 + angular_vel[b] * self.radius.1);
+        (vdel.dot(self.normal), vdel.dot(self.normal.perp()))
+    }
+
+    /// Pushes b along the normal and the tangent by the given impulses and a the opposite way.
+    /// The rolling impulse spins a up and b down
+    fn apply(&self, impulses: Impulses, vel: &mut [Vec2], angular_vel: &mut [f32]) {
+        let (a, b) = (self.a, self.b);
+        let linear = self.normal * impulses.normal + self.normal.perp() * impulses.tangent;
+        vel[a] -= linear * self.inv_mass.0;
+        vel[b] += linear * self.inv_mass.1;
+
+        angular_vel[a] -= (impulses.tangent * self.radius.0 - impulses.rolling) * self.inv_inertia.0;
+        angular_vel[b] -= (impulses.tangent * self.radius.1 + impulses.rolling) * self.inv_inertia.1;
+    }
+}
+
+/// Impulses each contact ended a tick with, keyed by the entities of the pair
+#[derive(Clone, Default)]
+pub struct WarmStart {
+    impulses: FxHashMap<(Entity, Entity), Impulses>,
+}
+
+impl WarmStart {
+    /// Approximate memory held, in bytes
+    pub fn size(&self) -> usize {
+        self.impulses.len() * std::mem::size_of::<((Entity, Entity), Impulses)>()
+    }
+}
+
+/// Sequential impulse solver for every contact at once. Each pass visits the contacts in turn,
+/// so a correction made early in the pass is seen by the rest, and dense packings are pushed
+/// apart over a few passes instead of piling up overlap tick after tick. Impulses carry over to
+/// the next tick to warm start it, which lets resting stacks settle in few passes
+#[derive(Default)]
+pub struct ContactSolver {
+    constraints: Vec<Constraint>,
+    /// Impulses of the last tick's contacts
+    warm: WarmStart,
+}
+
+impl ContactSolver {
+    /// Impulses the next tick will be warm started from
+    pub fn warm_start(&self) -> &WarmStart {
+        &self.warm
+    }
+
+    pub fn set_warm_start(&mut self, warm: WarmStart) {
+        self.warm = warm;
+    }
+
+    /// Resolves the touching or nearly touching `pairs` of `particles`, each with `a < b`. Works on
+    /// `to_pos`, `to_vel` and `to_angular_vel`. Mass goes with area and the circles are uniform discs
+    pub fn solve(&mut self, particles: &mut Particles, pairs: &[(u32, u32)], contact: &Contact, dt: f32) {
+        let p = particles;
+        let (vel, angular_vel) = (&mut p.to_vel, &mut p.to_angular_vel);
+
+        self.constraints.clear();
+        for (a, b) in pairs.iter().map(|(a, b)| (*a as usize, *b as usize)) {
+            let (ra, rb) = (p.radius[a], p.radius[b]);
+            let inv_mass = (1.0 / ra.powi(2), 1.0 / rb.powi(2));
+            let inv_inertia = (2.0 / ra.powi(4), 2.0 / rb.powi(4));
+
+            let del = p.pos[b] - p.pos[a];
+            let mut constraint = Constraint {
+                a,
+                b,
+                normal: del.normalize(),
+                radius: (ra, rb),
+                inv_mass,
+                inv_inertia,
+                normal_mass: 1.0 / (inv_mass.0 + inv_mass.1),
+                tangent_mass: 1.0 / (inv_mass.0 + inv_mass.1 + ra.powi(2) * inv_inertia.0 + rb.powi(2) * inv_inertia.1),
+                rolling_mass: 1.0 / (inv_inertia.0 + inv_inertia.1),
+                target: 0.0,
+                bounce: 0.0,
+                friction: contact.friction,
+                rolling_limit: contact.rolling_resistance * ra * rb / (ra + rb),
+                impulses: Impulses::default(),
+            };
+
+            let gap = del.length() - ra - rb;
+            let (normal_vel, _) = constraint.relative_vel(vel, angular_vel);
+            if gap > 0.0 {
+                constraint.target = -gap / dt;
+            } else if -normal_vel * dt > BOUNCE_THRESHOLD * ra.min(rb) {
+                constraint.bounce = -normal_vel * contact.restitution(p.kind[a], p.kind[b]);
+            }
+
+            // Pairs that have come apart start from nothing, what held them together is gone
+            if contact.warm_starting && gap <= 0.0 {
+                if let Some(impulses) = self.warm.impulses.get(&(p.entities[a], p.entities[b])) {
+                    constraint.impulses = *impulses;
+                    constraint.apply(*impulses, vel, angular_vel);
+                }
+            }
+            self.constraints.push(constraint);
+        }
+
+        for _ in 0..contact.iterations {
+            for c in &mut self.constraints {
+                let (normal_vel, tangent_vel) = c.relative_vel(vel, angular_vel);
+                let old = c.impulses;
+
+                // Pairs may only push apart
+                c.impulses.normal = (c.impulses.normal + (c.target - normal_vel) * c.normal_mass).max(0.0);
+                // Friction and rolling resistance are bounded by the normal impulse, which leaves
+                // the tangential velocity and the spins alone, so the values read above still hold
+                let limit = c.friction * c.impulses.normal;
+                c.impulses.tangent = (c.impulses.tangent - tangent_vel * c.tangent_mass).clamp(-limit, limit);
+                let limit = c.rolling_limit * c.impulses.normal;
+                let rolling = angular_vel[c.a] - angular_vel[c.b];
+                c.impulses.rolling = (c.impulses.rolling - rolling * c.rolling_mass).clamp(-limit, limit);
+
+                let delta = Impulses {
+                    normal: c.impulses.normal - old.normal,
+                    tangent: c.impulses.tangent - old.tangent,
+                    rolling: c.impulses.rolling - old.rolling,
+                };
+                c.apply(delta, vel, angular_vel);
+            }
+        }
+
+        // The bounce is left out of the impulses carried over, warm starting it would feed the same
+        // bounce back in every tick a contact persists
+        self.warm.impulses.clear();
+        if contact.warm_starting {
+            let entities = &p.entities;
+            self.warm.impulses.extend(
+                self.constraints
+                    .iter()
+                    .map(|c| ((entities[c.a], entities[c.b]), c.impulses)),
+            );
+        }
+
+        for c in self.constraints.iter_mut().filter(|c| c.bounce > 0.0) {
+            let (normal_vel, _) = c.relative_vel(vel, angular_vel);
+            let old = c.impulses.normal;
+            c.impulses.normal = (old + (c.bounce - normal_vel) * c.normal_mass).max(0.0);
+            let delta = Impulses {
+                normal: c.impulses.normal - old,
+                ..Default::default()
+            };
+            c.apply(delta, vel, angular_vel);
+        }
+
+        // Overlap is removed by moving the particles directly, so it adds no energy
+        let pos = &mut p.to_pos;
+        for _ in 0..contact.iterations {
+            for c in &self.constraints {
+                let del = pos[c.b] - pos[c.a];
+                let dist = del.length();
+                let overlap = c.radius.0 + c.radius.1 - dist;
+                if overlap > 0.0 && dist > 0.0 {
+                    let push = del / dist * overlap / (c.inv_mass.0 + c.inv_mass.1);
+                    pos[c.a] -= push * c.inv_mass.0;
+                    pos[c.b] += push * c.inv_mass.1;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use std::time::Duration;
+
+use super::{SpatialIndexKind, MAX_DENSE_CELLS};
+use crate::simulation::Config;
+
+/// Ticks each candidate cell size is timed for, the first of which is discarded as warm up
+const TRIAL_TICKS: u32 = 16;
+/// Ticks a tuned cell size is kept before tuning again
+const SETTLE_TICKS: u32 = 2000;
+/// Candidates are this many halvings below to doublings above the derived size
+const TRIAL_SPREAD: i32 = 2;
+
+/// Cell size matching the largest query distance, collisions reach two radii and forces
+/// reach the interaction radius or potential cutoff. Rounded up to a power of two as
+/// `DenseGrid` requires. A hierarchical grid only needs its first level to fit the smallest particles
+pub fn derive_cell_size(config: &Config) -> u32 {
+    let reach = match config.spatial_index {
+        SpatialIndexKind::HierarchicalGrid => config.reach(config.cell_radius),
+        _ => config.reach(config.max_radius()),
+    };
+    (reach.ceil().max(1.0) as u32).next_power_of_two()
+}
+
+enum Phase {
+    /// Using the configured or derived size as is
+    Fixed,
+    /// Timing `trials[trial]`
+    Trial { trial: usize, ticks: u32 },
+    /// Tuned, counts ticks until the next round of trials
+    Settled { ticks: u32 },
+}
+
+/// Picks the grid cell size. With `auto_tune_grid` set it times the steps at sizes around the
+/// derived one and keeps the fastest for a while, then tries again as the world changes
+pub struct GridTuner {
+    /// Size the trials are centred on, `grid_cell_size` or derived
+    base: u32,
+    trials: Vec<(u32, Duration)>,
+    phase: Phase,
+    /// Cell size in use
+    pub cell_size: u32,
+    /// Mean points tested per neighbour query, sampled each tick
+    pub candidates: f32,
+}
+
+impl GridTuner {
+    pub fn new(config: &Config) -> Self {
+        let base = base_cell_size(config);
+        Self {
+            base,
+            trials: Vec::new(),
+            phase: Phase::Fixed,
+            cell_size: base,
+            candidates: 0.0,
+        }
+    }
+
+    pub fn is_tuning(&self) -> bool {
+        matches!(self.phase, Phase::Trial { .. })
+    }
+
+    /// Records how long the last step's neighbour work took and returns the cell size for the next one
+    pub fn update(&mut self, config: &Config, elapsed: Duration) -> u32 {
+        let base = base_cell_size(config);
+        let tune = config.auto_tune_grid && config.spatial_index.uses_cells();
+
+        if base != self.base || !tune {
+            self.base = base;
+            self.cell_size = base;
+            self.phase = Phase::Fixed;
+        }
+        if !tune {
+            return self.cell_size;
+        }
+
+        self.phase = match self.phase {
+            Phase::Fixed => self.start_trials(config),
+            Phase::Settled { ticks } if ticks >= SETTLE_TICKS => self.start_trials(config),
+            Phase::Settled { ticks } => Phase::Settled { ticks: ticks + 1 },
+            Phase::Trial { trial, ticks } => {
+                if ticks > 0 {
+                    self.trials[trial].1 += elapsed;
+                }
+                match (ticks + 1 < TRIAL_TICKS, trial + 1 < self.trials.len()) {
+                    (true, _) => Phase::Trial { trial, ticks: ticks + 1 },
+                    (false, true) => Phase::Trial { trial: trial + 1, ticks: 0 },
+                    (false, false) => {
+                        self.cell_size = self.trials.iter().min_by_key(|(_, time)| *time).unwrap().0;
+                        Phase::Settled { ticks: 0 }
+                    }
+                }
+            }
+        };
+
+        if let Phase::Trial { trial, .. } = self.phase {
+            self.cell_size = self.trials[trial].0;
+        }
+        self.cell_size
+    }
+
+    fn start_trials(&mut self, config: &Config) -> Phase {
+        // A dense grid can't have cells bigger than the world, nor so small it needs more
+        // than `MAX_DENSE_CELLS` of them
+        let (smallest, largest) = match config.spatial_index {
+            SpatialIndexKind::DenseGrid => {
+                let side_len = config.bounds.1.x as u32;
+                let ncells_side = (MAX_DENSE_CELLS as f64).sqrt() as u32;
+                ((side_len / ncells_side).max(1), side_len)
+            }
+            _ => (1, u32::MAX),
+        };
+
+        self.trials = (-TRIAL_SPREAD..=TRIAL_SPREAD)
+            .map(|shift| match shift < 0 {
+                true => self.base >> -shift,
+                false => self.base.saturating_mul(1 << shift),
+            })
+            .filter(|size| (smallest..=largest).contains(size))
+            .map(|size| (size, Duration::ZERO))
+            .collect();
+        if self.trials.is_empty() {
+            self.trials.push((self.base.clamp(smallest, largest), Duration::ZERO));
+        }
+
+        Phase::Trial { trial: 0, ticks: 0 }
+    }
+}
+
+fn base_cell_size(config: &Config) -> u32 {
+    match config.grid_cell_size {
+        0 => derive_cell_size(config),
+        size => size,
+    }
+}
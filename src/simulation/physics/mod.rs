@@ -1,5 +1,25 @@
+mod barnes_hut;
+mod contact;
+mod contact_solver;
+mod grid_tuner;
+mod morton;
+mod neighbour_list;
+mod particles;
 pub mod physics;
 mod spatial_grid;
+mod spatial_index;
+mod spatial_query;
+mod thermostat;
 
-pub use physics::PhysicsPipeline;
+pub use barnes_hut::{BarnesHut, LongRange, LongRangeKind};
+pub use contact::Contact;
+pub use contact_solver::{ContactSolver, WarmStart};
+pub use grid_tuner::*;
+pub use morton::*;
+pub use neighbour_list::NeighbourList;
+pub use particles::Particles;
+pub use physics::{Carryover, PhysicsPipeline};
 pub use spatial_grid::*;
+pub use spatial_index::*;
+pub use spatial_query::SpatialQuery;
+pub use thermostat::{Thermostat, ThermostatKind};
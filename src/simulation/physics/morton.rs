@@ -0,0 +1,23 @@
+use glam::Vec2;
+
+/// Spreads the bits of `x` out so there is a zero between each of them
+fn part_1_by_1(x: u32) -> u64 {
+    let mut x = x as u64;
+    x = (x | (x << 16)) & 0x0000_ffff_0000_ffff;
+    x = (x | (x << 8)) & 0x00ff_00ff_00ff_00ff;
+    x = (x | (x << 4)) & 0x0f0f_0f0f_0f0f_0f0f;
+    x = (x | (x << 2)) & 0x3333_3333_3333_3333;
+    x = (x | (x << 1)) & 0x5555_5555_5555_5555;
+    x
+}
+
+/// Position along the Z-order curve of cell `(x, y)`, nearby cells get nearby codes
+pub fn morton_code(x: u32, y: u32) -> u64 {
+    part_1_by_1(x) | (part_1_by_1(y) << 1)
+}
+
+/// Z-order code of the grid cell holding `pos`, cells are counted from `origin`
+pub fn morton_cell(pos: Vec2, origin: Vec2, cell_size: f32) -> u64 {
+    let cell = ((pos - origin) / cell_size).max(Vec2::ZERO);
+    morton_code(cell.x as u32, cell.y as u32)
+}
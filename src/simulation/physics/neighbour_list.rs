@@ -0,0 +1,67 @@
+use glam::Vec2;
+use rayon::prelude::*;
+
+use super::SpatialIndex;
+
+/// Verlet neighbour lists. Every particle stores the ids within `cutoff + skin` of it, which stay
+/// a superset of its true neighbours until some particle has moved more than half the skin
+#[derive(Default)]
+pub struct NeighbourList {
+    /// `neighbours[starts[i]..starts[i + 1]]` are the neighbours of particle `i`
+    starts: Vec<u32>,
+    neighbours: Vec<u32>,
+    /// Positions the lists were built from
+    built_at: Vec<Vec2>,
+    /// Query distance the lists cover, skin included
+    reach: f32,
+    skin: f32,
+}
+
+impl NeighbourList {
+    /// Whether the lists can still be trusted for queries out to `cutoff` with this `skin`
+    pub fn is_valid(&self, pos: &[Vec2], cutoff: f32, skin: f32) -> bool {
+        let max_step2 = (0.5 * self.skin).powi(2);
+
+        pos.len() == self.built_at.len()
+            && skin == self.skin
+            && cutoff + skin <= self.reach
+            && pos
+                .par_iter()
+                .zip(self.built_at.par_iter())
+                .all(|(now, then)| now.distance_squared(*then) <= max_step2)
+    }
+
+    /// Rebuilds every list from `index`, which must hold `pos`
+    pub fn build(&mut self, index: &dyn SpatialIndex, pos: &[Vec2], cutoff: f32, skin: f32) {
+        self.reach = cutoff + skin;
+        self.skin = skin;
+        self.built_at.clear();
+        self.built_at.extend_from_slice(pos);
+
+        let reach = self.reach;
+        let lists: Vec<Vec<u32>> = pos
+            .par_iter()
+            .enumerate()
+            .map(|(i, pos)| index.query(*pos, reach, i as u32))
+            .collect();
+
+        self.starts.clear();
+        self.starts.push(0);
+        self.neighbours.clear();
+        for list in lists {
+            self.neighbours.extend(list);
+            self.starts.push(self.neighbours.len() as u32);
+        }
+    }
+
+    /// Forces a rebuild on the next `is_valid` check, for when ids stop matching particles
+    pub fn invalidate(&mut self) {
+        self.built_at.clear();
+    }
+
+    /// Listed neighbours of `i`, a superset of those within the cutoff
+    #[inline]
+    pub fn of(&self, i: usize) -> &[u32] {
+        &self.neighbours[self.starts[i] as usize..self.starts[i + 1] as usize]
+    }
+}
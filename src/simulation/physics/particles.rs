@@ -0,0 +1,149 @@
+use glam::Vec2;
+use legion::{Entity, IntoQuery, World};
+
+use crate::simulation::RigidCircle;
+
+/// Particle state as a structure of arrays, the form every physics stage works on.
+/// A particle's id is its index. Ids follow legion storage order until `permute` reorders them,
+/// and keep that order across gathers until particles are added or removed.
+/// Keeping every field in its own tightly packed array lets the per particle loops vectorise
+#[derive(Default)]
+pub struct Particles {
+    pub entities: Vec<Entity>,
+    pub pos: Vec<Vec2>,
+    pub vel: Vec<Vec2>,
+    /// Position and velocity at the end of the tick, where the interaction stages accumulate
+    pub to_pos: Vec<Vec2>,
+    pub to_vel: Vec<Vec2>,
+    pub angle: Vec<f32>,
+    pub angular_vel: Vec<f32>,
+    pub to_angular_vel: Vec<f32>,
+    pub radius: Vec<f32>,
+    pub kind: Vec<u8>,
+    /// Id of the particle at each place in storage, empty while ids follow storage order
+    order: Vec<u32>,
+}
+
+impl Particles {
+    /// Refills the arrays from the world's `RigidCircle`s
+    pub fn gather(&mut self, world: &World) {
+        if !self.order_holds(world) {
+            self.order.clear();
+            self.entities.clear();
+            self.entities
+                .extend(<(Entity, &RigidCircle)>::query().iter(world).map(|(entity, _)| *entity));
+        }
+
+        let n = self.entities.len();
+        self.pos.resize(n, Vec2::ZERO);
+        self.vel.resize(n, Vec2::ZERO);
+        self.to_pos.resize(n, Vec2::ZERO);
+        self.to_vel.resize(n, Vec2::ZERO);
+        self.angle.resize(n, 0.0);
+        self.angular_vel.resize(n, 0.0);
+        self.to_angular_vel.resize(n, 0.0);
+        self.radius.resize(n, 0.0);
+        self.kind.resize(n, 0);
+
+        for (stored, circ) in <&RigidCircle>::query().iter(world).enumerate() {
+            let i = self.id(stored);
+            self.pos[i] = circ.pos;
+            self.vel[i] = circ.vel;
+            self.to_pos[i] = circ.to_pos;
+            self.to_vel[i] = circ.to_vel;
+            self.angle[i] = circ.angle;
+            self.angular_vel[i] = circ.angular_vel;
+            self.to_angular_vel[i] = circ.to_angular_vel;
+            self.radius[i] = circ.radius;
+            self.kind[i] = circ.kind;
+        }
+    }
+
+    /// Writes the arrays back into the world. Must follow `gather` without entities being added
+    /// or removed in between, so storage order still matches ids
+    pub fn scatter(&self, world: &mut World) {
+        for (stored, (entity, circ)) in <(Entity, &mut RigidCircle)>::query().iter_mut(world).enumerate() {
+            let i = self.id(stored);
+            debug_assert!(*entity == self.entities[i]);
+            circ.pos = self.pos[i];
+            circ.vel = self.vel[i];
+            circ.to_pos = self.to_pos[i];
+            circ.to_vel = self.to_vel[i];
+            circ.angle = self.angle[i];
+            circ.angular_vel = self.angular_vel[i];
+            circ.to_angular_vel = self.to_angular_vel[i];
+        }
+    }
+
+    /// Moves the particle with id `by[i]` to id `i` in every array, so later gathers hand out
+    /// ids in the new order. Must follow `gather`
+    pub fn permute(&mut self, by: &[u32]) {
+        let mut new_id = vec![0; by.len()];
+        for (i, old) in by.iter().enumerate() {
+            new_id[*old as usize] = i as u32;
+        }
+        self.order = (0..by.len()).map(|stored| new_id[self.id(stored)]).collect();
+
+        permute(&mut self.entities, by);
+        permute(&mut self.pos, by);
+        permute(&mut self.vel, by);
+        permute(&mut self.to_pos, by);
+        permute(&mut self.to_vel, by);
+        permute(&mut self.angle, by);
+        permute(&mut self.angular_vel, by);
+        permute(&mut self.to_angular_vel, by);
+        permute(&mut self.radius, by);
+        permute(&mut self.kind, by);
+    }
+
+    /// Radius of the biggest particle, 0 with none
+    pub fn max_radius(&self) -> f32 {
+        self.radius.iter().fold(0.0f32, |max, radius| max.max(*radius))
+    }
+
+    /// Id of the particle at each place in storage, empty while ids follow storage order
+    pub fn order(&self) -> &[u32] {
+        &self.order
+    }
+
+    /// Hands out ids by `order` from the next gather on. `order` is what `order` returned when
+    /// the world's particles were stored the way they are now
+    pub fn set_order(&mut self, world: &World, order: &[u32]) {
+        let stored: Vec<Entity> = <(Entity, &RigidCircle)>::query()
+            .iter(world)
+            .map(|(entity, _)| *entity)
+            .collect();
+        self.order.clear();
+        if order.len() == stored.len() {
+            self.entities = stored.clone();
+            for (entity, id) in stored.into_iter().zip(order) {
+                self.entities[*id as usize] = entity;
+            }
+            self.order.extend_from_slice(order);
+        }
+    }
+
+    #[inline]
+    fn id(&self, stored: usize) -> usize {
+        self.order.get(stored).map_or(stored, |id| *id as usize)
+    }
+
+    /// Whether `order` still describes how the world's particles are stored, which stops being
+    /// true once any are added or removed
+    fn order_holds(&self, world: &World) -> bool {
+        if self.order.is_empty() {
+            return false;
+        }
+        let mut n = 0;
+        let same = <(Entity, &RigidCircle)>::query().iter(world).all(|(entity, _)| {
+            n += 1;
+            self.entities.get(self.id(n - 1)) == Some(entity)
+        });
+        same && n == self.entities.len()
+    }
+}
+
+/// Puts `values[by[i]]` at `i`
+fn permute<T: Copy>(values: &mut Vec<T>, by: &[u32]) {
+    *values = by.iter().map(|old| values[*old as usize]).collect();
+}
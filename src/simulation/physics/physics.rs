@@ -1,89 +1,664 @@
-use legion::{storage::Component, *};
+use std::{
+    f32::consts::TAU,
+    time::{Duration, Instant},
+};
+
+use glam::Vec2;
+use legion::*;
+use rayon::prelude::*;
 
 use super::{
-    spatial_grid::DenseGrid,
+    contact::Body, contact_solver::CONTACT_MARGIN, morton_cell, thermostat, BarnesHut, ContactSolver, GridTuner,
+    NeighbourList, Particles, SpatialIndex, WarmStart,
 };
 use crate::{
-    simulation::{Config, RigidCircle},
-    timing::timer::time_func,
+    simulation::{Config, Time},
+    timing::{timer::time_func, TIMING_DATABASE},
 };
 
+/// Particles sampled per tick to estimate the candidates per query
+const CANDIDATE_SAMPLES: usize = 64;
+
+/// State a tick hands to the next, see `PhysicsPipeline::carryover`
+#[derive(Clone, Default)]
+pub struct Carryover {
+    /// dt the adaptive timestep picked for the next tick
+    pub stable_dt: f32,
+    pub warm_start: WarmStart,
+    /// Grid cell size the next tick queries with, which decides the order neighbours are visited in
+    pub cell_size: u32,
+    /// Order the particles are numbered in, see `Particles::order`
+    pub order: Vec<u32>,
+}
+
+impl Carryover {
+    /// Approximate memory held, in bytes
+    pub fn size(&self) -> usize {
+        std::mem::size_of::<Self>() + self.warm_start.size() + self.order.len() * std::mem::size_of::<u32>()
+    }
+}
+
 pub struct PhysicsPipeline {
-    grid: DenseGrid,
+    index: Box<dyn SpatialIndex>,
+    /// Picks the cell size `index` is created with
+    tuner: GridTuner,
+    cell_size: u32,
+    /// Scratch buffer of every particle's position and id, the input to the spatial index
+    points: Vec<(Vec2, u32)>,
+    /// Working copy of the particles, synced with the world at the start and end of a step
+    particles: Particles,
+    /// Used instead of querying `index` when `verlet_skin` is set
+    neighbour_list: NeighbourList,
+    /// Rebuilt every step while `long_range` is on
+    barnes_hut: BarnesHut,
+    /// Used instead of a single pass over contacts when `contact.iterations` is above 1
+    contact_solver: ContactSolver,
+    /// Where every particle started the tick, kept while `contact.ccd_threshold` is set
+    swept_from: Vec<Vec2>,
+    /// Collisions found by sweeping that the particles would otherwise have passed through
+    tunnelling_avoided: u64,
+    /// dt the next tick may take while `timestep.adaptive` is on
+    stable_dt: f32,
+    /// Measured at the end of the last step
+    temperature: f32,
 }
 
 impl PhysicsPipeline {
     pub fn new(_world: &mut World, config: &Config) -> Self {
-        let grid = DenseGrid::new((config.cell_radius * 32.0) as u32, (config.bounds.1.x) as u32);
-
-        Self { grid }
+        let tuner = GridTuner::new(config);
+        let cell_size = tuner.cell_size;
+        Self {
+            index: config.spatial_index.create(config, cell_size),
+            tuner,
+            cell_size,
+            points: Vec::new(),
+            particles: Particles::default(),
+            neighbour_list: NeighbourList::default(),
+            barnes_hut: BarnesHut::default(),
+            contact_solver: ContactSolver::default(),
+            swept_from: Vec::new(),
+            tunnelling_avoided: 0,
+            stable_dt: config.timestep.min_dt,
+            temperature: 0.0,
+        }
     }
 
     pub fn step(&mut self, world: &mut World, resources: &mut Resources) {
         time_func!(physics, step);
 
-        self.update_positions(world, resources);
-        self.detect_collisions(world);
+        let config = *resources.get::<Config>().unwrap();
+        let dt = resources.get::<Time>().unwrap().dt();
+        let tick = resources.get::<Time>().unwrap().tick;
+        self.gather(world);
+        if config.reorder_interval > 0 && tick.is_multiple_of(config.reorder_interval as u128) {
+            self.reorder(&config);
+        }
+
+        let start = Instant::now();
+        let substeps = config.substeps.max(1);
+        let mut substep_config = config;
+        if let Some(iterations) = config.substep_contact_iterations {
+            substep_config.contact.iterations = iterations;
+        }
+        for substep in 0..substeps {
+            let step = tick as u64 * substeps as u64 + substep as u64;
+            self.substep(&substep_config, dt / substeps as f32, step);
+        }
+        let elapsed = start.elapsed();
+        time_reorder_gain(&config, tick, elapsed);
+        self.tune_grid(&config, elapsed);
+
+        self.scatter(world);
+    }
+
+    /// Moves the particles on by `dt` and resolves what meeting each other does to them.
+    /// `step` counts substeps over the whole run and seeds anything random
+    fn substep(&mut self, config: &Config, dt: f32, step: u64) {
+        time_func!(physics, substep);
+
+        self.update_positions(config, dt);
+        if config.verlet_skin > 0.0 {
+            self.update_neighbour_list(config);
+        } else {
+            self.build_index();
+        }
+        if self.sweep_fast(config, dt) {
+            // Particles were moved back along their paths, after the index was built
+            match config.verlet_skin > 0.0 {
+                true => self.update_neighbour_list(config),
+                false => self.build_index(),
+            }
+        }
+        self.apply_fields(config, dt);
+        self.apply_forces(config, dt);
+        self.apply_potentials(config, dt);
+        self.apply_long_range(config, dt);
+        self.plan_timestep(config, dt);
+        self.detect_collisions(config, dt);
+        self.apply_thermostat(config, dt, step);
+    }
+
+    /// What the next tick takes over from this one besides the particles, for rewinding
+    pub fn carryover(&self) -> Carryover {
+        Carryover {
+            stable_dt: self.stable_dt,
+            warm_start: self.contact_solver.warm_start().clone(),
+            cell_size: self.cell_size,
+            order: self.particles.order().to_vec(),
+        }
+    }
+
+    /// Picks up from `carryover` after the particles in `world` and `config` were rewound, and
+    /// drops the caches built for the particles as they were
+    pub fn rewind(&mut self, world: &World, carryover: &Carryover, config: &Config) {
+        self.stable_dt = carryover.stable_dt;
+        self.particles.set_order(world, &carryover.order);
+        self.contact_solver.set_warm_start(carryover.warm_start.clone());
+        self.neighbour_list.invalidate();
+
+        self.tuner.cell_size = carryover.cell_size;
+        if carryover.cell_size != self.cell_size {
+            self.cell_size = carryover.cell_size;
+            self.index = config.spatial_index.create(config, self.cell_size);
+        }
+    }
+
+    /// Largest dt the next tick can take and keep particles from moving too far, see `Timestep`
+    pub fn stable_dt(&self) -> f32 {
+        self.stable_dt
+    }
+
+    /// Mean translational kinetic energy per particle, see `Thermostat`
+    pub fn temperature(&self) -> f32 {
+        self.temperature
+    }
+
+    /// Grid cell size tuning and statistics
+    pub fn tuner(&self) -> &GridTuner {
+        &self.tuner
+    }
+
+    /// Collisions caught by continuous collision detection since the pipeline was created, each
+    /// one a pair that would otherwise have passed through each other
+    pub fn tunnelling_avoided(&self) -> u64 {
+        self.tunnelling_avoided
+    }
+
+    /// Samples the cost of queries, then lets the tuner pick the cell size for the next step
+    fn tune_grid(&mut self, config: &Config, elapsed: Duration) {
+        let p = &self.particles;
+        let stride = (p.pos.len() / CANDIDATE_SAMPLES).max(1);
+        let (sum, count) = (0..p.pos.len()).step_by(stride).fold((0, 0), |(sum, count), i| {
+            (sum + self.index.candidates(p.pos[i], config.reach(p.radius[i])), count + 1)
+        });
+        self.tuner.candidates = sum as f32 / count.max(1) as f32;
+
+        let cell_size = self.tuner.update(config, elapsed);
+        if cell_size != self.cell_size {
+            self.cell_size = cell_size;
+            self.index = config.spatial_index.create(config, cell_size);
+            // The lists were built from the old index, which is gone
+            self.neighbour_list.invalidate();
+        }
+    }
+
+    /// Renumbers the particles in Z-order of grid cells so particles close in space are close
+    /// in memory, and so get nearby ids. The world and its entities are left as they are
+    fn reorder(&mut self, config: &Config) {
+        time_func!(physics, reorder);
+
+        let (origin, cell_size) = (config.bounds.0, self.cell_size as f32);
+        let pos = &self.particles.pos;
+        let mut by: Vec<u32> = (0..pos.len() as u32).collect();
+        by.par_sort_by_cached_key(|i| morton_cell(pos[*i as usize], origin, cell_size));
+
+        self.particles.permute(&by);
+        self.neighbour_list.invalidate();
+    }
+
+    fn gather(&mut self, world: &World) {
+        time_func!(physics, gather);
+        self.particles.gather(world);
+    }
+
+    fn scatter(&self, world: &mut World) {
+        time_func!(physics, scatter);
+        self.particles.scatter(world);
     }
 
-    fn update_positions(&mut self, world: &mut World, resources: &Resources) {
+    fn update_positions(&mut self, config: &Config, dt: f32) {
         time_func!(physics, pos_update);
 
-        let bounds = resources.get::<Config>().unwrap().bounds;
+        let (bounds, open) = (config.bounds, config.open_bounds);
+        let p = &mut self.particles;
+        if config.contact.ccd_threshold > 0.0 {
+            self.swept_from.clone_from(&p.to_pos);
+        }
+
+        (&mut p.angle, &mut p.angular_vel, &p.to_angular_vel)
+            .into_par_iter()
+            .for_each(|(angle, angular_vel, to_angular_vel)| {
+                *angular_vel = *to_angular_vel;
+                *angle = (*angle + *angular_vel * dt).rem_euclid(TAU);
+            });
+
+        (&mut p.pos, &mut p.vel, &mut p.to_pos, &mut p.to_vel, &p.radius)
+            .into_par_iter()
+            .for_each(|(pos, vel, to_pos, to_vel, radius)| {
+                *vel = *to_vel;
+                *pos = *to_pos + *vel * dt;
+
+                if !open {
+                    if (pos.x - radius) <= bounds.0.x || (pos.x + radius) >= bounds.1.x {
+                        pos.x = pos.x.clamp(bounds.0.x + radius, bounds.1.x - radius);
+                        vel.x = -vel.x;
+                    }
+                    if (pos.y - radius) <= bounds.0.y || (pos.y + radius) > bounds.1.y {
+                        pos.y = pos.y.clamp(bounds.0.y + radius, bounds.1.y - radius);
+                        vel.y = -vel.y;
+                    }
+                }
+
+                *to_vel = *vel;
+                *to_pos = *pos;
+            });
+    }
+
+    /// Measures the fastest particle and the strongest force, relative to particle size, once the
+    /// forces are in. Collisions are left out, they only trade momentum between neighbours and
+    /// would shrink the step at every impact
+    fn plan_timestep(&mut self, config: &Config, dt: f32) {
+        if !config.timestep.adaptive || dt <= 0.0 {
+            return;
+        }
+
+        let p = &self.particles;
+        let (speed, accel) = (&p.vel, &p.to_vel, &p.radius)
+            .into_par_iter()
+            .map(|(vel, to_vel, radius)| (to_vel.length() / radius, (*to_vel - *vel).length() / dt / radius))
+            .reduce(|| (0.0, 0.0), |a, b| (a.0.max(b.0), a.1.max(b.1)));
+        // The tick is split evenly between the substeps
+        self.stable_dt = config.timestep.stable_dt(speed, accel, dt) * config.substeps.max(1) as f32;
+    }
+
+    fn build_index(&mut self) {
+        time_func!(physics, index_build);
+
+        self.points.clear();
+        self.points
+            .extend(self.particles.pos.iter().enumerate().map(|(i, pos)| (*pos, i as u32)));
+        self.index.build(&self.points, &self.particles.radius);
+    }
+
+    /// Continuous collision detection. Sweeps every particle that moved further than
+    /// `ccd_threshold` of its radius this tick against the others' paths, and bounces it off the
+    /// first one it hits if the two would otherwise have passed through each other. Walls clamp
+    /// positions, so nothing tunnels through them. Returns whether any particle was moved
+    fn sweep_fast(&mut self, config: &Config, dt: f32) -> bool {
+        time_func!(physics, ccd);
+
+        let (contact, threshold) = (config.contact, config.contact.ccd_threshold);
+        if threshold <= 0.0 {
+            return false;
+        }
+
+        let steps: Vec<f32> = self
+            .particles
+            .pos
+            .par_iter()
+            .zip(self.swept_from.par_iter())
+            .map(|(to, from)| to.distance(*from))
+            .collect();
+        let radius = &self.particles.radius;
+        let fast: Vec<usize> = (0..steps.len())
+            .filter(|i| steps[*i] > threshold * radius[*i])
+            .collect();
+        if fast.is_empty() {
+            return false;
+        }
+        if config.verlet_skin > 0.0 {
+            // The lists may still be valid, in which case the index is left over from an older tick
+            self.build_index();
+        }
 
-        self.grid.clear();
-        <(Entity, &mut RigidCircle)>::query().par_for_each_mut(world, |(entity, circ)| {
-            circ.vel = circ.to_vel;
-            circ.pos = circ.to_pos + circ.vel;
+        let p = &self.particles;
+        let (from, radius) = (&self.swept_from, &p.radius);
+        let step = |i: usize| p.pos[i] - from[i];
+        let max_radius = p.max_radius();
+        // The index holds where particles ended up, every path lies within the longest step of that.
+        // Slow particles move too, so the longest is taken over all of them
+        let max_step = steps.iter().fold(0.0f32, |max, step| max.max(*step));
 
-            if (circ.pos.x - circ.radius) <= bounds.0.x || (circ.pos.x + circ.radius) >= bounds.1.x {
-                circ.pos.x = circ.pos.x.clamp(bounds.0.x + circ.radius, bounds.1.x - circ.radius);
-                circ.vel.x = -circ.vel.x;
+        let index = self.index.as_ref();
+        let mut hits: Vec<Hit> = fast
+            .par_iter()
+            .filter_map(|i| {
+                let i = *i;
+                let mut first: Option<Hit> = None;
+                let mut sweep = |j: usize| {
+                    let hit = time_of_impact(from[j] - from[i], step(j) - step(i), radius[i] + radius[j]);
+                    if let Some((t, tunnelled)) = hit.filter(|(t, _)| first.is_none_or(|first| *t < first.t)) {
+                        let (a, b) = (i.min(j), i.max(j));
+                        first = Some(Hit { a, b, t, tunnelled });
+                    }
+                };
+
+                let reach = 0.5 * steps[i] + radius[i] + max_radius + max_step;
+                index.for_each_within(from[i] + 0.5 * step(i), reach, i as u32, &mut |j| sweep(j as usize));
+                first
+            })
+            .collect();
+        // Two fast particles that hit each other both find the pair
+        hits.sort_unstable_by(|x, y| x.t.partial_cmp(&y.t).unwrap().then((x.a, x.b).cmp(&(y.a, y.b))));
+        hits.dedup_by_key(|hit| (hit.a, hit.b));
+
+        let p = &mut self.particles;
+        let from = &self.swept_from;
+        let mut bounced = vec![false; p.pos.len()];
+        let (bounds, open) = (config.bounds, config.open_bounds);
+        for Hit { a, b, t, tunnelled } in hits {
+            // Earliest hits first, a particle only bounces once a tick
+            if bounced[a] || bounced[b] {
+                continue;
             }
-            if (circ.pos.y - circ.radius) <= bounds.0.y || (circ.pos.y + circ.radius) > bounds.1.y {
-                circ.pos.y = circ.pos.y.clamp(bounds.0.y + circ.radius, bounds.1.y - circ.radius);
-                circ.vel.y = -circ.vel.y;
+            bounced[a] = true;
+            bounced[b] = true;
+            self.tunnelling_avoided += tunnelled as u64;
+
+            let body = |i: usize| Body {
+                vel: (p.pos[i] - from[i]) / dt,
+                angular_vel: p.angular_vel[i],
+                radius: p.radius[i],
+            };
+            let (body_a, body_b) = (body(a), body(b));
+            let at_a = from[a] + body_a.vel * t * dt;
+            let at_b = from[b] + body_b.vel * t * dt;
+            let restitution = contact.restitution(p.kind[a], p.kind[b]);
+
+            for (i, at, this, other, del) in [
+                (a, at_a, body_a, body_b, at_b - at_a),
+                (b, at_b, body_b, body_a, at_a - at_b),
+            ] {
+                let response = contact.resolve(del, this, other, restitution, dt);
+                let vel = this.vel + response.vel;
+                let mut pos = at + vel * (1.0 - t) * dt;
+                if !open {
+                    pos = pos.clamp(bounds.0 + Vec2::splat(this.radius), bounds.1 - Vec2::splat(this.radius));
+                }
+
+                p.pos[i] = pos;
+                p.to_pos[i] = pos;
+                p.vel[i] = vel;
+                p.to_vel[i] = vel;
+                p.to_angular_vel[i] += response.angular_vel;
             }
+        }
+        true
+    }
+
+    /// Rebuilds the index and neighbour lists once a particle may have moved into range of one
+    /// that isn't listed
+    fn update_neighbour_list(&mut self, config: &Config) {
+        let max_radius = self.particles.max_radius();
+        let cutoff = config.reach(max_radius);
+        let skin = config.verlet_skin;
+
+        if self.neighbour_list.is_valid(&self.particles.pos, cutoff, skin) {
+            return;
+        }
+
+        self.build_index();
+        time_func!(physics, neighbour_list);
+        self.neighbour_list.build(self.index.as_ref(), &self.particles.pos, cutoff, skin);
+    }
 
-            circ.to_vel = circ.vel;
-            circ.to_pos = circ.pos;
-            self.grid.insert(circ.pos, *entity);
+    /// External fields, which accelerate every particle the same whatever its size
+    fn apply_fields(&mut self, config: &Config, dt: f32) {
+        time_func!(physics, fields);
+
+        let (fields, seed) = (config.fields, config.seed);
+        if fields.is_empty() {
+            return;
+        }
+
+        let p = &mut self.particles;
+        (&mut p.to_vel, &p.pos).into_par_iter().for_each(|(to_vel, pos)| {
+            *to_vel += fields.accel(*pos, seed) * dt;
         });
     }
 
-    fn detect_collisions(&self, world: &mut World) {
-        time_func!(physics, col_detect);
+    /// Particle life forces and force curves between every pair within their range
+    fn apply_forces(&mut self, config: &Config, dt: f32) {
+        time_func!(physics, forces);
 
-        let mut q = <(Entity, &mut RigidCircle)>::query().filter(component::<RigidCircle>());
-        unsafe {
-            q.par_for_each_unchecked(world, |(ent, c)| {
-                let around = self.grid.query(c.pos, 2.0 * c.radius, *ent);
-                around.iter().for_each(|e| {
-                    elastic_collision(c, self.unsafe_component(world, *e));
-                });
+        let (rules, curves) = (config.rules, config.force_curves);
+        if rules.is_inert() && curves.is_inert() {
+            return;
+        }
+        let retained = (1.0 - rules.damping).powf(dt);
+        let range = match rules.is_inert() {
+            true => curves.range(),
+            false => curves.range().max(rules.interaction_radius),
+        };
+
+        let neighbours = Neighbours::new(config, self.index.as_ref(), &self.neighbour_list);
+        let p = &mut self.particles;
+        let (pos, kind) = (&p.pos, &p.kind);
+
+        p.to_vel.par_iter_mut().enumerate().for_each(|(i, to_vel)| {
+            let mut force = Vec2::ZERO;
+            neighbours.for_each_within(i, pos, range, &mut |j| {
+                let j = j as usize;
+                let del = pos[j] - pos[i];
+                force += rules.force(kind[i], kind[j], del) + curves.force(kind[i], kind[j], del);
             });
+            *to_vel = (*to_vel + force * dt) * retained;
+        });
+    }
+
+    /// Pair potentials between every pair within their range. Mass goes with area
+    fn apply_potentials(&mut self, config: &Config, dt: f32) {
+        time_func!(physics, potentials);
+
+        let potentials = config.potentials;
+        if potentials.is_inert() {
+            return;
         }
+        let range = potentials.range();
+
+        let neighbours = Neighbours::new(config, self.index.as_ref(), &self.neighbour_list);
+        let p = &mut self.particles;
+        let (pos, radius, kind) = (&p.pos, &p.radius, &p.kind);
+
+        p.to_vel.par_iter_mut().enumerate().for_each(|(i, to_vel)| {
+            let mut force = Vec2::ZERO;
+            neighbours.for_each_within(i, pos, range, &mut |j| {
+                let j = j as usize;
+                force += potentials.force(kind[i], kind[j], pos[j] - pos[i]);
+            });
+            *to_vel += force / radius[i].powi(2) * dt;
+        });
     }
 
-    fn unsafe_component<'a, T: Component>(&self, world: &'a World, entity: Entity) -> &'a mut T {
-        unsafe {
-            world
-                .entry_ref(entity)
-                .unwrap()
-                .into_component_unchecked::<T>()
-                .unwrap()
+    /// Gravity or electrostatics between every pair, summed with a Barnes-Hut tree
+    fn apply_long_range(&mut self, config: &Config, dt: f32) {
+        time_func!(physics, long_range);
+
+        let long_range = config.long_range;
+        if long_range.is_off() {
+            return;
         }
+
+        let p = &mut self.particles;
+        let (pos, radius, kind) = (&p.pos, &p.radius, &p.kind);
+        self.barnes_hut.build(pos, |i| long_range.source(kind[i], radius[i]));
+
+        let tree = &self.barnes_hut;
+        p.to_vel.par_iter_mut().enumerate().for_each(|(i, to_vel)| {
+            let field = tree.field(pos[i], i as u32, long_range.theta, long_range.softening);
+            *to_vel += field * long_range.response(kind[i], radius[i]) * dt;
+        });
+    }
+
+    /// Pulls the temperature towards the thermostat's target, then measures it
+    fn apply_thermostat(&mut self, config: &Config, dt: f32, step: u64) {
+        time_func!(physics, thermostat);
+
+        let p = &mut self.particles;
+        let seed = config.seed ^ step.wrapping_mul(0xD1B5_4A32_D192_ED03);
+        config.thermostat.apply(&mut p.to_vel, &p.radius, dt, seed);
+        self.temperature = thermostat::temperature(&p.to_vel, &p.radius);
     }
+
+    fn detect_collisions(&mut self, config: &Config, dt: f32) {
+        time_func!(physics, col_detect);
+
+        let contact = config.contact;
+        let neighbours = Neighbours::new(config, self.index.as_ref(), &self.neighbour_list);
+        if contact.iterations > 1 {
+            let pairs = contact_pairs(&neighbours, &self.particles);
+            time_func!(physics, contact_solve);
+            self.contact_solver.solve(&mut self.particles, &pairs, &contact, dt);
+            return;
+        }
+
+        let p = &mut self.particles;
+        let (pos, vel, angular_vel, radius, kind) = (&p.pos, &p.vel, &p.angular_vel, &p.radius, &p.kind);
+        let body = |i: usize| Body {
+            vel: vel[i],
+            angular_vel: angular_vel[i],
+            radius: radius[i],
+        };
+        let max_radius = p.max_radius();
+
+        (&mut p.to_pos, &mut p.to_vel, &mut p.to_angular_vel)
+            .into_par_iter()
+            .enumerate()
+            .for_each(|(i, (to_pos, to_vel, to_angular_vel))| {
+                neighbours.for_each_overlapping(i, pos, radius[i], max_radius, &mut |j| {
+                    let j = j as usize;
+                    let del = pos[j] - pos[i];
+                    let dist2 = del.length_squared();
+                    // Exactly coincident circles have no direction to separate along
+                    if dist2 > 0.0 && dist2 < (radius[i] + radius[j]).powi(2) {
+                        let restitution = contact.restitution(kind[i], kind[j]);
+                        let response = contact.resolve(del, body(i), body(j), restitution, dt);
+                        *to_pos += response.pos;
+                        *to_vel += response.vel;
+                        *to_angular_vel += response.angular_vel;
+                    }
+                });
+            });
+    }
+}
+
+/// First contact between a pair of particles found by sweeping, `a < b`
+#[derive(Clone, Copy)]
+struct Hit {
+    a: usize,
+    b: usize,
+    /// Fraction of the tick at which they touch
+    t: f32,
+    /// Whether they would have ended the tick apart or past each other, so the collision stage
+    /// would never have seen them collide
+    tunnelled: bool,
+}
+
+/// Fraction of the tick at which two circles `del` apart, moving apart by `vdel` over the tick,
+/// first touch when their radii sum to `reach`, and whether they would have tunnelled through
+/// each other. `None` if they don't meet this tick or start out overlapping
+fn time_of_impact(del: Vec2, vdel: Vec2, reach: f32) -> Option<(f32, bool)> {
+    let a = vdel.length_squared();
+    let b = 2.0 * del.dot(vdel);
+    let c = del.length_squared() - reach.powi(2);
+    let discriminant = b.powi(2) - 4.0 * a * c;
+    if c <= 0.0 || a <= 0.0 || discriminant < 0.0 {
+        return None;
+    }
+
+    let t = (-b - discriminant.sqrt()) / (2.0 * a);
+    let end = del + vdel;
+    let tunnelled = end.length_squared() >= reach.powi(2) || end.dot(del) < 0.0;
+    (0.0..=1.0).contains(&t).then_some((t, tunnelled))
+}
+
+/// Every pair of particles `(i, j)` with `i < j` that touch or are within `CONTACT_MARGIN` of it
+fn contact_pairs(neighbours: &Neighbours, particles: &Particles) -> Vec<(u32, u32)> {
+    let (pos, radius) = (&particles.pos, &particles.radius);
+    let max_radius = particles.max_radius();
+
+    (0..pos.len())
+        .into_par_iter()
+        .flat_map_iter(|i| {
+            let mut found = Vec::new();
+            let reach = radius[i] * (1.0 + CONTACT_MARGIN);
+            neighbours.for_each_overlapping(i, pos, reach, max_radius, &mut |j| {
+                let j = j as usize;
+                let margin = CONTACT_MARGIN * radius[i].min(radius[j]);
+                let dist2 = pos[i].distance_squared(pos[j]);
+                // Exactly coincident circles have no direction to separate along
+                if j > i && dist2 > 0.0 && dist2 < (radius[i] + radius[j] + margin).powi(2) {
+                    found.push((i as u32, j as u32));
+                }
+            });
+            found
+        })
+        .collect()
 }
 
-/// Elastic collision between two circles.
-/// Updates RigidCircles in place
-fn elastic_collision(a: &mut RigidCircle, b: &RigidCircle) {
-    let del = b.pos - a.pos;
-    let dist = del.length();
-    let norm = dist.powi(2);
-    let vdel = b.vel - a.vel;
+/// Records `elapsed`, how long the tick's substeps took, as `after_reorder` on the tick particles
+/// were reordered and as `before_reorder` on the tick before the next reorder. The gap between them
+/// is what reordering wins back as particles drift out of order
+fn time_reorder_gain(config: &Config, tick: u128, elapsed: Duration) {
+    let interval = config.reorder_interval as u128;
+    if interval < 2 {
+        return;
+    }
 
-    a.to_vel += ((vdel).dot(del) / norm) * del;
-    a.to_pos -= del / dist * (a.radius * 2.0 - dist) * 0.5;
+    let mut database = TIMING_DATABASE.write();
+    if tick.is_multiple_of(interval) {
+        database.physics.after_reorder.update(elapsed);
+    } else if (tick + 1).is_multiple_of(interval) {
+        database.physics.before_reorder.update(elapsed);
+    }
+}
+
+/// How the interaction stages find neighbours this step
+enum Neighbours<'a> {
+    Index(&'a dyn SpatialIndex),
+    List(&'a NeighbourList),
+}
+
+impl<'a> Neighbours<'a> {
+    fn new(config: &Config, index: &'a dyn SpatialIndex, list: &'a NeighbourList) -> Self {
+        match config.verlet_skin > 0.0 {
+            true => Neighbours::List(list),
+            false => Neighbours::Index(index),
+        }
+    }
+
+    /// Calls `visit` on every particle within `radius` of particle `i`
+    #[inline]
+    fn for_each_within(&self, i: usize, pos: &[Vec2], radius: f32, visit: &mut dyn FnMut(u32)) {
+        match self {
+            Neighbours::Index(index) => index.for_each_within(pos[i], radius, i as u32, visit),
+            Neighbours::List(list) => {
+                let radius2 = radius.powi(2);
+                for j in list.of(i) {
+                    if pos[i].distance_squared(pos[*j as usize]) < radius2 {
+                        visit(*j);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Calls `visit` on every particle whose circle could overlap particle `i`'s
+    #[inline]
+    fn for_each_overlapping(&self, i: usize, pos: &[Vec2], radius: f32, max_radius: f32, visit: &mut dyn FnMut(u32)) {
+        match self {
+            Neighbours::Index(index) => index.for_each_overlapping(pos[i], radius, max_radius, i as u32, visit),
+            Neighbours::List(_) => self.for_each_within(i, pos, radius + max_radius, visit),
+        }
+    }
 }
@@ -0,0 +1,158 @@
+use glam::Vec2;
+use rayon::prelude::*;
+
+use super::{DenseGrid, SparseGrid, MAX_DENSE_CELLS};
+use crate::simulation::physics::{bounds_of, expanding_k_nearest, SpatialIndex};
+
+/// Stack of grids whose cell size doubles from one level to the next. A particle belongs to the
+/// first level whose cells are at least as wide as it is. Each level keeps a grid of its own
+/// particles and one of every particle up to its size, so overlap queries only look at the few
+/// cells around a particle on every level however much the radii vary
+pub struct HierarchicalGrid {
+    /// Cell size of level 0
+    base_cell: f32,
+    /// Levels are dense grids over `0..side_len` when set and small enough, sparse grids otherwise
+    side_len: Option<u32>,
+    /// Particles of each level
+    levels: Vec<Box<dyn SpatialIndex>>,
+    /// Particles of each level and every level below it, at that level's cell size
+    up_to: Vec<Box<dyn SpatialIndex>>,
+    /// Points of each level, build scratch
+    buckets: Vec<Vec<(Vec2, u32)>>,
+    /// Points up to each level, build scratch
+    cumulative: Vec<Vec<(Vec2, u32)>>,
+    /// Bounding box of the points, limits nearest neighbour searches
+    min: Vec2,
+    max: Vec2,
+}
+
+impl HierarchicalGrid {
+    pub fn new(base_cell: u32, side_len: Option<u32>) -> Self {
+        Self {
+            base_cell: base_cell.max(1) as f32,
+            side_len,
+            levels: Vec::new(),
+            up_to: Vec::new(),
+            buckets: Vec::new(),
+            cumulative: Vec::new(),
+            min: Vec2::ZERO,
+            max: Vec2::ZERO,
+        }
+    }
+
+    /// Level whose cells fit a circle of `radius`
+    fn level_of(&self, radius: f32) -> usize {
+        (2.0 * radius / self.base_cell).max(1.0).log2().ceil() as usize
+    }
+
+    fn cell_size(&self, level: usize) -> f32 {
+        self.base_cell * (1 << level) as f32
+    }
+
+    fn new_level(&self, level: usize) -> Box<dyn SpatialIndex> {
+        let cell_size = self.cell_size(level) as u32;
+        match self.side_len {
+            Some(side_len) if ((side_len / cell_size.min(side_len)) as u64).pow(2) <= MAX_DENSE_CELLS => {
+                Box::new(DenseGrid::new(cell_size.min(side_len), side_len))
+            }
+            _ => Box::new(SparseGrid::new(cell_size)),
+        }
+    }
+}
+
+impl SpatialIndex for HierarchicalGrid {
+    fn build(&mut self, points: &[(Vec2, u32)], radii: &[f32]) {
+        let largest = radii.iter().fold(0.0f32, |max, radius| max.max(*radius));
+        let n_levels = self.level_of(largest) + 1;
+
+        while self.levels.len() < n_levels {
+            let level = self.levels.len();
+            self.levels.push(self.new_level(level));
+            self.up_to.push(self.new_level(level));
+            self.buckets.push(Vec::new());
+            self.cumulative.push(Vec::new());
+        }
+        self.levels.truncate(n_levels);
+        self.up_to.truncate(n_levels);
+        self.buckets.truncate(n_levels);
+        self.cumulative.truncate(n_levels);
+
+        self.buckets.iter_mut().for_each(|bucket| bucket.clear());
+        for (pos, id) in points {
+            let level = self.level_of(radii[*id as usize]);
+            self.buckets[level].push((*pos, *id));
+        }
+
+        for level in 0..n_levels {
+            let (below, rest) = self.cumulative.split_at_mut(level);
+            let cumulative = &mut rest[0];
+            cumulative.clear();
+            if let Some(below) = below.last() {
+                cumulative.extend_from_slice(below);
+            }
+            cumulative.extend_from_slice(&self.buckets[level]);
+        }
+
+        self.levels
+            .par_iter_mut()
+            .zip(self.buckets.par_iter())
+            .for_each(|(level, bucket)| level.build(bucket, radii));
+        self.up_to
+            .par_iter_mut()
+            .zip(self.cumulative.par_iter())
+            .for_each(|(level, points)| level.build(points, radii));
+
+        let (min, max) = bounds_of(points.iter().map(|(pos, _)| *pos));
+        self.min = min;
+        self.max = max;
+    }
+
+    fn query(&self, pos: Vec2, radius: f32, ignore: u32) -> Vec<u32> {
+        let mut hits = Vec::with_capacity(4);
+        self.for_each_within(pos, radius, ignore, &mut |id| hits.push(id));
+        hits
+    }
+
+    fn for_each_within(&self, pos: Vec2, radius: f32, ignore: u32, visit: &mut dyn FnMut(u32)) {
+        for level in self.levels.iter() {
+            level.for_each_within(pos, radius, ignore, visit);
+        }
+    }
+
+    /// Particles no bigger than this one are found on its own level among everything up to it,
+    /// bigger ones level by level above. Either way a level's circles are at most half its cell
+    /// size, so every search covers only the cells around the particle
+    fn for_each_overlapping(&self, pos: Vec2, radius: f32, max_radius: f32, ignore: u32, visit: &mut dyn FnMut(u32)) {
+        if self.levels.is_empty() {
+            return;
+        }
+        let own = self.level_of(radius).min(self.levels.len() - 1);
+        let reach = |level: usize| radius + (0.5 * self.cell_size(level)).min(max_radius);
+
+        self.up_to[own].for_each_within(pos, reach(own), ignore, visit);
+        for level in own + 1..self.levels.len() {
+            if !self.buckets[level].is_empty() {
+                self.levels[level].for_each_within(pos, reach(level), ignore, visit);
+            }
+        }
+    }
+
+    fn for_each_point_within(&self, pos: Vec2, radius: f32, visit: &mut dyn FnMut(Vec2, u32)) {
+        for level in self.levels.iter() {
+            level.for_each_point_within(pos, radius, visit);
+        }
+    }
+
+    fn candidates(&self, pos: Vec2, radius: f32) -> usize {
+        self.levels.iter().map(|level| level.candidates(pos, radius)).sum()
+    }
+
+    fn query_aabb(&self, min: Vec2, max: Vec2) -> Vec<u32> {
+        self.levels.iter().flat_map(|level| level.query_aabb(min, max)).collect()
+    }
+
+    fn k_nearest(&self, pos: Vec2, k: usize) -> Vec<u32> {
+        let limit = (self.max.max(pos) - self.min.min(pos)).length();
+        expanding_k_nearest(self, pos, k, self.base_cell, limit)
+    }
+}
@@ -1,2 +1,6 @@
+mod hierarchical_grid;
+mod sparse_grid;
 pub mod spatial_grid;
-pub use spatial_grid::DenseGrid;
+pub use hierarchical_grid::HierarchicalGrid;
+pub use sparse_grid::SparseGrid;
+pub use spatial_grid::{DenseGrid, MAX_DENSE_CELLS};
@@ -0,0 +1,141 @@
+use dashmap::DashMap;
+use fxhash::FxBuildHasher;
+use glam::Vec2;
+use itertools::Itertools;
+use rayon::prelude::*;
+
+use crate::simulation::physics::{bounds_of, expanding_k_nearest, SpatialIndex};
+
+type Cell = Vec<(Vec2, u32)>;
+
+/// Uniform grid that only stores occupied cells, hashed by their integer coordinates.
+/// Coordinates are unbounded so it works for open worlds and huge, mostly empty ones
+pub struct SparseGrid {
+    cell_size: f32,
+    cells: DashMap<(i32, i32), Cell, FxBuildHasher>,
+    /// Cells in the map as of the last build. Counting them locks every shard of the map
+    n_cells: usize,
+    /// Bounding box of the points, limits nearest neighbour searches
+    min: Vec2,
+    max: Vec2,
+}
+
+impl SparseGrid {
+    pub fn new(cell_size: u32) -> Self {
+        Self {
+            cell_size: cell_size as f32,
+            cells: DashMap::with_hasher(FxBuildHasher::default()),
+            n_cells: 0,
+            min: Vec2::ZERO,
+            max: Vec2::ZERO,
+        }
+    }
+
+    pub fn insert(&self, pos: Vec2, id: u32) {
+        self.cells.entry(self.cell_key(pos)).or_default().push((pos, id));
+    }
+
+    #[inline]
+    pub fn cell_key(&self, pos: Vec2) -> (i32, i32) {
+        let cell = (pos / self.cell_size).floor();
+        (cell.x as i32, cell.y as i32)
+    }
+
+    /// Empties every cell. Cells left empty since the previous clear are freed,
+    /// the rest keep their allocation for the next build
+    pub fn clear(&mut self) {
+        self.cells.retain(|_, cell| !cell.is_empty());
+        self.cells.iter_mut().for_each(|mut cell| cell.clear());
+    }
+
+    pub fn cell_range(&self, min: Vec2, max: Vec2) -> impl Iterator<Item = (i32, i32)> {
+        let (x1, y1) = self.cell_key(min);
+        let (x2, y2) = self.cell_key(max);
+
+        (x1..=x2).cartesian_product(y1..=y2)
+    }
+
+    /// Calls `visit` on every point in the cells overlapping the box spanned by `min` and `max`
+    fn visit<F: FnMut(&(Vec2, u32))>(&self, min: Vec2, max: Vec2, mut visit: F) {
+        // A box wider than the occupied area is cheaper to answer by walking the map
+        let span = (max - min) / self.cell_size + Vec2::ONE;
+        if span.x * span.y > self.n_cells as f32 {
+            self.cells.iter().for_each(|cell| cell.iter().for_each(&mut visit));
+            return;
+        }
+
+        for key in self.cell_range(min, max) {
+            if let Some(cell) = self.cells.get(&key) {
+                cell.iter().for_each(&mut visit);
+            }
+        }
+    }
+}
+
+impl SpatialIndex for SparseGrid {
+    fn build(&mut self, points: &[(Vec2, u32)], _radii: &[f32]) {
+        self.clear();
+        points.par_iter().for_each(|(pos, id)| self.insert(*pos, *id));
+        self.n_cells = self.cells.len();
+
+        let (min, max) = bounds_of(points.iter().map(|(pos, _)| *pos));
+        self.min = min;
+        self.max = max;
+    }
+
+    fn query(&self, pos: Vec2, radius: f32, ignore: u32) -> Vec<u32> {
+        let radius2 = radius.powi(2);
+        let mut hits = Vec::with_capacity(4);
+
+        self.visit(pos - Vec2::splat(radius), pos + Vec2::splat(radius), |(other, id)| {
+            if (*id != ignore) & (pos.distance_squared(*other) < radius2) {
+                hits.push(*id);
+            }
+        });
+
+        hits
+    }
+
+    fn for_each_within(&self, pos: Vec2, radius: f32, ignore: u32, visit: &mut dyn FnMut(u32)) {
+        let radius2 = radius.powi(2);
+
+        self.visit(pos - Vec2::splat(radius), pos + Vec2::splat(radius), |(other, id)| {
+            if (*id != ignore) & (pos.distance_squared(*other) < radius2) {
+                visit(*id);
+            }
+        });
+    }
+
+    fn for_each_point_within(&self, pos: Vec2, radius: f32, visit: &mut dyn FnMut(Vec2, u32)) {
+        let radius2 = radius.powi(2);
+
+        self.visit(pos - Vec2::splat(radius), pos + Vec2::splat(radius), |(other, id)| {
+            if pos.distance_squared(*other) < radius2 {
+                visit(*other, *id);
+            }
+        });
+    }
+
+    fn candidates(&self, pos: Vec2, radius: f32) -> usize {
+        let mut candidates = 0;
+        self.visit(pos - Vec2::splat(radius), pos + Vec2::splat(radius), |_| candidates += 1);
+        candidates
+    }
+
+    fn query_aabb(&self, min: Vec2, max: Vec2) -> Vec<u32> {
+        let mut hits = Vec::new();
+
+        self.visit(min, max, |(pos, id)| {
+            if pos.cmpge(min).all() && pos.cmple(max).all() {
+                hits.push(*id);
+            }
+        });
+
+        hits
+    }
+
+    fn k_nearest(&self, pos: Vec2, k: usize) -> Vec<u32> {
+        let limit = (self.max.max(pos) - self.min.min(pos)).length();
+        expanding_k_nearest(self, pos, k, self.cell_size, limit)
+    }
+}
@@ -1,24 +1,43 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
 use glam::Vec2;
 use itertools::Itertools;
-use legion::Entity;
-use parking_lot::RwLock;
+use rayon::prelude::*;
 
-type Cell = Vec<(Vec2, Entity)>;
+use crate::simulation::physics::{expanding_k_nearest, SpatialIndex};
 
 const U32_SIZE: u32 = (std::mem::size_of::<u32>() as u32) * 8;
 
+/// Most cells a dense grid is built with. Hierarchical levels that would need more are sparse
+/// instead, and the grid tuner doesn't try cells small enough to need more
+pub const MAX_DENSE_CELLS: u64 = 1 << 20;
+
+/// Cell index of points that fall outside the grid
+const OUTSIDE: u32 = u32::MAX;
+
 fn log_2(x: u32) -> u32 {
     debug_assert!(x > 0);
     U32_SIZE - x.leading_zeros() - 1
 }
 
+/// Uniform grid over `0..side_len` on both axes, stored as one array of points sorted by cell.
+/// Built with a parallel counting sort, so neither building nor querying takes a lock
 pub struct DenseGrid {
+    side_len: u32,
+    cell_size: u32,
     /// log2(ncells_side)
     log2_side: u32,
     /// log2(cell_size)
     log2_cell: u32,
 
-    cells: Vec<RwLock<Cell>>,
+    /// `points[starts[i]..starts[i + 1]]` are the points in cell `i`
+    starts: Vec<u32>,
+    points: Vec<(Vec2, u32)>,
+
+    /// Build scratch, points per cell and then the next free slot of each cell
+    counts: Vec<AtomicU32>,
+    /// Build scratch, cell of every input point
+    point_cells: Vec<u32>,
 }
 
 impl DenseGrid {
@@ -26,60 +45,173 @@ impl DenseGrid {
         assert!(side_len.is_power_of_two());
         assert!(cell_size.is_power_of_two());
         let ncells_side = side_len / cell_size;
+        let ncells = (ncells_side as u64).pow(2) as usize;
         Self {
+            side_len,
+            cell_size,
             log2_side: log_2(ncells_side),
             log2_cell: log_2(cell_size),
-            cells: (0..(ncells_side * ncells_side))
-                .map(|_| RwLock::new(Cell::default()))
-                .collect(),
+            starts: vec![0; ncells + 1],
+            points: Vec::new(),
+            counts: (0..ncells).map(|_| AtomicU32::new(0)).collect(),
+            point_cells: Vec::new(),
         }
     }
 
-    pub fn insert(&self, pos: Vec2, entity: Entity) {
-        if let Some(cell) = self.cells.get(self.flat_ind(pos)) {
-            cell.write().push((pos, entity));
-        }
+    #[inline]
+    fn ncells_side(&self) -> u32 {
+        1 << self.log2_side
     }
 
+    /// Cell holding `pos`, or `OUTSIDE` past the far edges. Negative coordinates clamp to the first row/column
     #[inline]
-    pub fn flat_ind(&self, pos: Vec2) -> usize {
+    pub fn flat_ind(&self, pos: Vec2) -> u32 {
         let x = (pos.x as u32) >> self.log2_cell;
         let y = (pos.y as u32) >> self.log2_cell;
-        ((y << self.log2_side) | x) as usize
+        match x < self.ncells_side() && y < self.ncells_side() {
+            true => (y << self.log2_side) | x,
+            false => OUTSIDE,
+        }
     }
 
-    pub fn clear(&mut self) {
-        self.cells.iter_mut().for_each(|cell| cell.write().clear());
+    /// Points of a single cell
+    #[inline]
+    fn cell(&self, ind: u32) -> &[(Vec2, u32)] {
+        let ind = ind as usize;
+        &self.points[self.starts[ind] as usize..self.starts[ind + 1] as usize]
     }
 
-    pub fn query(&self, pos: Vec2, radius: f32, ignore: Entity) -> Vec<Entity> {
-        let radius2 = radius.powi(2);
+    pub fn query(&self, pos: Vec2, radius: f32, ignore: u32) -> Vec<u32> {
         let mut hits = Vec::with_capacity(4);
+        self.for_each_within(pos, radius, ignore, &mut |id| hits.push(id));
+        hits
+    }
+
+    /// Cells overlapping the box spanned by `min` and `max`, clipped to the grid
+    pub fn cell_range(&self, min: Vec2, max: Vec2) -> impl Iterator<Item = u32> {
+        let last = self.ncells_side() - 1;
+        let x1 = ((min.x as u32) >> self.log2_cell).min(last);
+        let y1 = ((min.y as u32) >> self.log2_cell).min(last);
+        let x2 = ((max.x as u32) >> self.log2_cell).min(last);
+        let y2 = ((max.y as u32) >> self.log2_cell).min(last);
 
-        for ind in self.cell_range(pos, radius) {
-            if let Some(cell) = self.cells.get(ind as usize) {
-                // We know this is at a read only stage. Safe to disregard lock
-                let unlocked = unsafe { cell.data_ptr().as_ref().unwrap() };
-                hits.extend(unlocked.iter().filter_map(|(other, id)| {
-                    match (*id != ignore) & (pos.distance_squared(*other) < radius2) {
-                        true => Some(*id),
-                        false => None,
-                    }
-                }));
+        let shift = self.log2_side;
+
+        (x1..=x2).cartesian_product(y1..=y2).map(move |(x, y)| (y << shift) | x)
+    }
+}
+
+impl SpatialIndex for DenseGrid {
+    /// Counting sort by cell: count the points in every cell, prefix sum the counts into
+    /// cell starts, then scatter every point into its cell's range. Points outside the grid are dropped
+    fn build(&mut self, points: &[(Vec2, u32)], _radii: &[f32]) {
+        self.counts.par_iter_mut().for_each(|count| *count.get_mut() = 0);
+
+        let mut point_cells = std::mem::take(&mut self.point_cells);
+        let (counts, grid) = (&self.counts, &*self);
+        points
+            .par_iter()
+            .map(|(pos, _)| {
+                let ind = grid.flat_ind(*pos);
+                if ind != OUTSIDE {
+                    counts[ind as usize].fetch_add(1, Ordering::Relaxed);
+                }
+                ind
+            })
+            .collect_into_vec(&mut point_cells);
+
+        let mut total = 0;
+        for (start, count) in self.starts.iter_mut().zip(self.counts.iter_mut()) {
+            *start = total;
+            total += *count.get_mut();
+            // Reused as the next free slot in the cell while scattering
+            *count.get_mut() = *start;
+        }
+        *self.starts.last_mut().unwrap() = total;
+
+        self.points.clear();
+        if let Some(first) = points.first() {
+            self.points.resize(total as usize, *first);
+        }
+
+        let slots = SharedSlice(self.points.as_mut_ptr());
+        let counts = &self.counts;
+        points.par_iter().zip(point_cells.par_iter()).for_each(|(point, ind)| {
+            if *ind != OUTSIDE {
+                let slot = counts[*ind as usize].fetch_add(1, Ordering::Relaxed);
+                // Every slot below `total` is handed out exactly once by the fetch_add above,
+                // so no two threads write to the same element
+                unsafe { slots.write(slot as usize, *point) };
             }
+        });
+
+        self.point_cells = point_cells;
+    }
+
+    fn query(&self, pos: Vec2, radius: f32, ignore: u32) -> Vec<u32> {
+        DenseGrid::query(self, pos, radius, ignore)
+    }
+
+    fn for_each_within(&self, pos: Vec2, radius: f32, ignore: u32, visit: &mut dyn FnMut(u32)) {
+        let radius2 = radius.powi(2);
+
+        for ind in self.cell_range(pos - Vec2::splat(radius), pos + Vec2::splat(radius)) {
+            for (other, id) in self.cell(ind) {
+                if (*id != ignore) & (pos.distance_squared(*other) < radius2) {
+                    visit(*id);
+                }
+            }
+        }
+    }
+
+    fn for_each_point_within(&self, pos: Vec2, radius: f32, visit: &mut dyn FnMut(Vec2, u32)) {
+        let radius2 = radius.powi(2);
+
+        for ind in self.cell_range(pos - Vec2::splat(radius), pos + Vec2::splat(radius)) {
+            for (other, id) in self.cell(ind) {
+                if pos.distance_squared(*other) < radius2 {
+                    visit(*other, *id);
+                }
+            }
+        }
+    }
+
+    fn candidates(&self, pos: Vec2, radius: f32) -> usize {
+        self.cell_range(pos - Vec2::splat(radius), pos + Vec2::splat(radius))
+            .map(|ind| self.cell(ind).len())
+            .sum()
+    }
+
+    fn query_aabb(&self, min: Vec2, max: Vec2) -> Vec<u32> {
+        let mut hits = Vec::new();
+
+        for ind in self.cell_range(min, max) {
+            hits.extend(self.cell(ind).iter().filter_map(|(pos, id)| {
+                match pos.cmpge(min).all() && pos.cmple(max).all() {
+                    true => Some(*id),
+                    false => None,
+                }
+            }));
         }
 
         hits
     }
 
-    pub fn cell_range(&self, pos: Vec2, radius: f32) -> impl Iterator<Item = u32> {
-        let x1 = ((pos.x - radius) as u32) >> self.log2_cell;
-        let y1 = ((pos.y - radius) as u32) >> self.log2_cell;
-        let x2 = ((pos.x + radius) as u32) >> self.log2_cell;
-        let y2 = ((pos.y + radius) as u32) >> self.log2_cell;
+    fn k_nearest(&self, pos: Vec2, k: usize) -> Vec<u32> {
+        let limit = self.side_len as f32 * std::f32::consts::SQRT_2;
+        expanding_k_nearest(self, pos, k, self.cell_size as f32, limit)
+    }
+}
+
+/// Lets rayon workers write disjoint elements of one slice
+struct SharedSlice<T>(*mut T);
 
-        let shift = self.log2_side;
+unsafe impl<T: Send> Send for SharedSlice<T> {}
+unsafe impl<T: Send> Sync for SharedSlice<T> {}
 
-        (x1..=x2).cartesian_product(y1..=y2).map(move |(x, y)| (y << shift) | x)
+impl<T> SharedSlice<T> {
+    /// Caller guarantees `ind` is in bounds and no other thread touches it concurrently
+    unsafe fn write(&self, ind: usize, value: T) {
+        self.0.add(ind).write(value);
     }
 }
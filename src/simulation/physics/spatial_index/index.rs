@@ -0,0 +1,131 @@
+use std::cmp::Ordering;
+
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+use super::{QuadTree, SortAndSweep};
+use crate::simulation::{
+    physics::{DenseGrid, HierarchicalGrid, SparseGrid},
+    Config,
+};
+
+/// Broadphase structure answering proximity queries over particle positions,
+/// each tagged with the particle's id in `Particles`. Rebuilt from scratch every tick, then only read from
+pub trait SpatialIndex: Send + Sync {
+    /// Replaces the contents of the index with `points`, `radii` holds each point's radius by id
+    fn build(&mut self, points: &[(Vec2, u32)], radii: &[f32]);
+
+    /// Every id within `radius` of `pos`, excluding `ignore`
+    fn query(&self, pos: Vec2, radius: f32, ignore: u32) -> Vec<u32>;
+
+    /// Same as `query` but hands each id to `visit` as it is found. This is what the physics
+    /// stages call for every particle, so indexes should override it to avoid allocating
+    fn for_each_within(&self, pos: Vec2, radius: f32, ignore: u32, visit: &mut dyn FnMut(u32)) {
+        self.query(pos, radius, ignore).into_iter().for_each(visit)
+    }
+
+    /// Superset of the ids whose circle could overlap the circle of `radius` at `pos`, given no circle
+    /// is bigger than `max_radius`. Callers check the actual overlap
+    fn for_each_overlapping(&self, pos: Vec2, radius: f32, max_radius: f32, ignore: u32, visit: &mut dyn FnMut(u32)) {
+        self.for_each_within(pos, radius + max_radius, ignore, visit)
+    }
+
+    /// Hands every stored point within `radius` of `pos` to `visit` along with its id
+    fn for_each_point_within(&self, pos: Vec2, radius: f32, visit: &mut dyn FnMut(Vec2, u32));
+
+    /// Points tested to answer a query out to `radius` around `pos`, how much work it costs
+    fn candidates(&self, pos: Vec2, radius: f32) -> usize {
+        self.query(pos, radius, u32::MAX).len()
+    }
+
+    /// Every id inside the axis aligned box spanned by `min` and `max`
+    fn query_aabb(&self, min: Vec2, max: Vec2) -> Vec<u32>;
+
+    /// Up to `k` ids closest to `pos`, nearest first
+    fn k_nearest(&self, pos: Vec2, k: usize) -> Vec<u32>;
+}
+
+/// Which `SpatialIndex` the physics pipeline uses
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpatialIndexKind {
+    /// Preallocated grid over `bounds`, points outside of it are dropped
+    #[default]
+    DenseGrid,
+    /// Hashed grid that only stores occupied cells, works with `open_bounds`
+    SparseGrid,
+    /// Grids of doubling cell size, each particle in the level that fits its radius. Dense within
+    /// `bounds`, sparse with `open_bounds`. For worlds mixing very small and very large particles
+    HierarchicalGrid,
+    SortAndSweep,
+    QuadTree,
+}
+
+impl SpatialIndexKind {
+    /// Index for `config`, grids get cells of side `cell_size`
+    pub fn create(&self, config: &Config, cell_size: u32) -> Box<dyn SpatialIndex> {
+        match self {
+            SpatialIndexKind::DenseGrid => {
+                let side_len = config.bounds.1.x as u32;
+                Box::new(DenseGrid::new(cell_size.min(side_len), side_len))
+            }
+            SpatialIndexKind::SparseGrid => Box::new(SparseGrid::new(cell_size)),
+            SpatialIndexKind::HierarchicalGrid => {
+                let side_len = (!config.open_bounds).then(|| config.bounds.1.x as u32);
+                Box::new(HierarchicalGrid::new(cell_size, side_len))
+            }
+            SpatialIndexKind::SortAndSweep => Box::new(SortAndSweep::default()),
+            SpatialIndexKind::QuadTree => Box::new(QuadTree::default()),
+        }
+    }
+
+    /// Whether the index is a grid, so has a cell size to tune
+    pub fn uses_cells(&self) -> bool {
+        matches!(
+            self,
+            SpatialIndexKind::DenseGrid | SpatialIndexKind::SparseGrid | SpatialIndexKind::HierarchicalGrid
+        )
+    }
+}
+
+/// Smallest axis aligned box holding every point, as its min and max corners
+pub(crate) fn bounds_of(points: impl IntoIterator<Item = Vec2>) -> (Vec2, Vec2) {
+    points
+        .into_iter()
+        .fold((Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)), |(min, max), pos| {
+            (min.min(pos), max.max(pos))
+        })
+}
+
+/// Every stored point within `radius` of `pos`
+fn points_within<I: SpatialIndex + ?Sized>(index: &I, pos: Vec2, radius: f32) -> Vec<(Vec2, u32)> {
+    let mut hits = Vec::new();
+    index.for_each_point_within(pos, radius, &mut |other, id| hits.push((other, id)));
+    hits
+}
+
+/// k nearest neighbours for indexes without a native search: query circles of doubling radius,
+/// starting at `radius`, until `k` points are found or `limit` is passed
+pub(crate) fn expanding_k_nearest<I>(index: &I, pos: Vec2, k: usize, mut radius: f32, limit: f32) -> Vec<u32>
+where
+    I: SpatialIndex + ?Sized,
+{
+    if k == 0 {
+        return Vec::new();
+    }
+    radius = radius.max(f32::EPSILON);
+
+    loop {
+        let mut found = points_within(index, pos, radius);
+        if found.len() >= k || radius > limit {
+            // Anything outside the circle is further than everything inside it
+            found.sort_by(|a, b| {
+                pos.distance_squared(a.0)
+                    .partial_cmp(&pos.distance_squared(b.0))
+                    .unwrap_or(Ordering::Equal)
+            });
+            return found.into_iter().take(k).map(|(_, id)| id).collect();
+        }
+        radius *= 2.0;
+    }
+}
@@ -0,0 +1,8 @@
+mod index;
+mod quad_tree;
+mod sort_and_sweep;
+
+pub(crate) use index::{bounds_of, expanding_k_nearest};
+pub use index::{SpatialIndex, SpatialIndexKind};
+pub use quad_tree::QuadTree;
+pub use sort_and_sweep::SortAndSweep;
@@ -0,0 +1,274 @@
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use glam::Vec2;
+
+use super::{bounds_of, SpatialIndex};
+
+/// Most points a node holds before it is split
+const LEAF_SIZE: usize = 16;
+/// Stops splitting stacks of coincident points forever
+const MAX_DEPTH: u32 = 24;
+
+struct Node {
+    min: Vec2,
+    max: Vec2,
+    /// Range of `QuadTree::points` inside this node
+    start: usize,
+    end: usize,
+    /// Index of the first of four consecutive children
+    children: Option<usize>,
+}
+
+impl Node {
+    /// Squared distance from `pos` to the closest point of the node's box
+    fn distance_squared(&self, pos: Vec2) -> f32 {
+        (pos.max(self.min).min(self.max) - pos).length_squared()
+    }
+}
+
+/// Region quadtree over the points' bounding box. Adapts to clustered and sparse worlds
+/// and answers nearest neighbour searches without scanning by radius
+#[derive(Default)]
+pub struct QuadTree {
+    nodes: Vec<Node>,
+    /// Points ordered so every node owns a contiguous range
+    points: Vec<(Vec2, u32)>,
+}
+
+impl QuadTree {
+    fn split(&mut self, node: usize, depth: u32) {
+        let Node { min, max, start, end, .. } = self.nodes[node];
+        if end - start <= LEAF_SIZE || depth >= MAX_DEPTH {
+            return;
+        }
+
+        let center = (min + max) * 0.5;
+        let points = &mut self.points[start..end];
+        let bottom = partition(points, |pos| pos.y < center.y);
+        let bottom_left = partition(&mut points[..bottom], |pos| pos.x < center.x);
+        let top_left = partition(&mut points[bottom..], |pos| pos.x < center.x);
+
+        let bounds = [
+            (min, center),
+            (Vec2::new(center.x, min.y), Vec2::new(max.x, center.y)),
+            (Vec2::new(min.x, center.y), Vec2::new(center.x, max.y)),
+            (center, max),
+        ];
+        let splits = [
+            start,
+            start + bottom_left,
+            start + bottom,
+            start + bottom + top_left,
+            end,
+        ];
+
+        let first = self.nodes.len();
+        self.nodes[node].children = Some(first);
+        for (i, (min, max)) in bounds.iter().enumerate() {
+            self.nodes.push(Node {
+                min: *min,
+                max: *max,
+                start: splits[i],
+                end: splits[i + 1],
+                children: None,
+            });
+        }
+        for child in first..first + 4 {
+            self.split(child, depth + 1);
+        }
+    }
+
+    /// Calls `visit` on every point in a node accepted by `enter`
+    fn walk<E, V>(&self, enter: E, mut visit: V)
+    where
+        E: Fn(&Node) -> bool,
+        V: FnMut(&(Vec2, u32)),
+    {
+        if self.nodes.is_empty() {
+            return;
+        }
+
+        let mut stack = vec![0];
+        while let Some(ind) = stack.pop() {
+            let node = &self.nodes[ind];
+            if !enter(node) {
+                continue;
+            }
+            match node.children {
+                Some(first) => stack.extend(first..first + 4),
+                None => self.points[node.start..node.end].iter().for_each(&mut visit),
+            }
+        }
+    }
+}
+
+impl SpatialIndex for QuadTree {
+    fn build(&mut self, points: &[(Vec2, u32)], _radii: &[f32]) {
+        self.points.clear();
+        self.points.extend_from_slice(points);
+        self.nodes.clear();
+
+        if points.is_empty() {
+            return;
+        }
+
+        let (min, max) = bounds_of(points.iter().map(|(pos, _)| *pos));
+        // Square root node so every child is square too
+        let side = (max - min).max_element();
+        self.nodes.push(Node {
+            min,
+            max: min + Vec2::splat(side),
+            start: 0,
+            end: points.len(),
+            children: None,
+        });
+        self.split(0, 0);
+    }
+
+    fn query(&self, pos: Vec2, radius: f32, ignore: u32) -> Vec<u32> {
+        let radius2 = radius.powi(2);
+        let mut hits = Vec::with_capacity(4);
+
+        self.walk(
+            |node| node.distance_squared(pos) < radius2,
+            |(other, id)| {
+                if (*id != ignore) & (pos.distance_squared(*other) < radius2) {
+                    hits.push(*id);
+                }
+            },
+        );
+
+        hits
+    }
+
+    fn for_each_within(&self, pos: Vec2, radius: f32, ignore: u32, visit: &mut dyn FnMut(u32)) {
+        let radius2 = radius.powi(2);
+
+        self.walk(
+            |node| node.distance_squared(pos) < radius2,
+            |(other, id)| {
+                if (*id != ignore) & (pos.distance_squared(*other) < radius2) {
+                    visit(*id);
+                }
+            },
+        );
+    }
+
+    fn for_each_point_within(&self, pos: Vec2, radius: f32, visit: &mut dyn FnMut(Vec2, u32)) {
+        let radius2 = radius.powi(2);
+
+        self.walk(
+            |node| node.distance_squared(pos) < radius2,
+            |(other, id)| {
+                if pos.distance_squared(*other) < radius2 {
+                    visit(*other, *id);
+                }
+            },
+        );
+    }
+
+    fn candidates(&self, pos: Vec2, radius: f32) -> usize {
+        let radius2 = radius.powi(2);
+        let mut candidates = 0;
+        self.walk(|node| node.distance_squared(pos) < radius2, |_| candidates += 1);
+        candidates
+    }
+
+    fn query_aabb(&self, min: Vec2, max: Vec2) -> Vec<u32> {
+        let mut hits = Vec::new();
+
+        self.walk(
+            |node| node.min.cmple(max).all() && node.max.cmpge(min).all(),
+            |(pos, id)| {
+                if pos.cmpge(min).all() && pos.cmple(max).all() {
+                    hits.push(*id);
+                }
+            },
+        );
+
+        hits
+    }
+
+    /// Best first search, nodes and points are visited in order of distance
+    fn k_nearest(&self, pos: Vec2, k: usize) -> Vec<u32> {
+        let mut found = Vec::with_capacity(k);
+        if self.nodes.is_empty() || k == 0 {
+            return found;
+        }
+
+        let mut heap = BinaryHeap::new();
+        heap.push(Candidate {
+            dist2: self.nodes[0].distance_squared(pos),
+            item: Item::Node(0),
+        });
+
+        while let Some(Candidate { item, .. }) = heap.pop() {
+            match item {
+                Item::Point(id) => {
+                    found.push(id);
+                    if found.len() == k {
+                        break;
+                    }
+                }
+                Item::Node(ind) => {
+                    let node = &self.nodes[ind];
+                    match node.children {
+                        Some(first) => heap.extend((first..first + 4).map(|child| Candidate {
+                            dist2: self.nodes[child].distance_squared(pos),
+                            item: Item::Node(child),
+                        })),
+                        None => heap.extend(self.points[node.start..node.end].iter().map(|(other, id)| Candidate {
+                            dist2: pos.distance_squared(*other),
+                            item: Item::Point(*id),
+                        })),
+                    }
+                }
+            }
+        }
+
+        found
+    }
+}
+
+enum Item {
+    Node(usize),
+    Point(u32),
+}
+
+/// Heap entry ordered so the closest candidate is popped first
+struct Candidate {
+    dist2: f32,
+    item: Item,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.dist2 == other.dist2
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.dist2.partial_cmp(&self.dist2).unwrap_or(Ordering::Equal)
+    }
+}
+
+/// Moves every element matching `pred` to the front, returning how many matched
+fn partition<F: Fn(Vec2) -> bool>(points: &mut [(Vec2, u32)], pred: F) -> usize {
+    let mut split = 0;
+    for i in 0..points.len() {
+        if pred(points[i].0) {
+            points.swap(split, i);
+            split += 1;
+        }
+    }
+    split
+}
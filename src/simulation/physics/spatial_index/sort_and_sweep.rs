@@ -0,0 +1,94 @@
+use glam::Vec2;
+use rayon::prelude::*;
+
+use super::{bounds_of, expanding_k_nearest, SpatialIndex};
+
+/// Points sorted along x. Queries binary search the x interval they cover and sweep it,
+/// so nothing is allocated up front and there is no cell size to tune
+#[derive(Default)]
+pub struct SortAndSweep {
+    points: Vec<(Vec2, u32)>,
+    /// x coordinate of every point, kept apart for cache friendly binary searches
+    xs: Vec<f32>,
+    min: Vec2,
+    max: Vec2,
+}
+
+impl SortAndSweep {
+    /// Points whose x lies in `min_x..=max_x`
+    fn sweep(&self, min_x: f32, max_x: f32) -> &[(Vec2, u32)] {
+        let start = self.xs.partition_point(|x| *x < min_x);
+        let end = self.xs.partition_point(|x| *x <= max_x);
+        &self.points[start..end.max(start)]
+    }
+}
+
+impl SpatialIndex for SortAndSweep {
+    fn build(&mut self, points: &[(Vec2, u32)], _radii: &[f32]) {
+        self.points.clear();
+        self.points.extend_from_slice(points);
+        self.points
+            .par_sort_unstable_by(|a, b| a.0.x.partial_cmp(&b.0.x).unwrap_or(std::cmp::Ordering::Equal));
+
+        self.xs.clear();
+        self.xs.extend(self.points.iter().map(|(pos, _)| pos.x));
+
+        let (min, max) = bounds_of(self.points.iter().map(|(pos, _)| *pos));
+        self.min = min;
+        self.max = max;
+    }
+
+    fn query(&self, pos: Vec2, radius: f32, ignore: u32) -> Vec<u32> {
+        let radius2 = radius.powi(2);
+        self.sweep(pos.x - radius, pos.x + radius)
+            .iter()
+            .filter_map(|(other, id)| match (*id != ignore) & (pos.distance_squared(*other) < radius2) {
+                true => Some(*id),
+                false => None,
+            })
+            .collect()
+    }
+
+    fn for_each_within(&self, pos: Vec2, radius: f32, ignore: u32, visit: &mut dyn FnMut(u32)) {
+        let radius2 = radius.powi(2);
+
+        for (other, id) in self.sweep(pos.x - radius, pos.x + radius) {
+            if (*id != ignore) & (pos.distance_squared(*other) < radius2) {
+                visit(*id);
+            }
+        }
+    }
+
+    fn for_each_point_within(&self, pos: Vec2, radius: f32, visit: &mut dyn FnMut(Vec2, u32)) {
+        let radius2 = radius.powi(2);
+
+        for (other, id) in self.sweep(pos.x - radius, pos.x + radius) {
+            if pos.distance_squared(*other) < radius2 {
+                visit(*other, *id);
+            }
+        }
+    }
+
+    fn candidates(&self, pos: Vec2, radius: f32) -> usize {
+        self.sweep(pos.x - radius, pos.x + radius).len()
+    }
+
+    fn query_aabb(&self, min: Vec2, max: Vec2) -> Vec<u32> {
+        self.sweep(min.x, max.x)
+            .iter()
+            .filter_map(|(pos, id)| match pos.y >= min.y && pos.y <= max.y {
+                true => Some(*id),
+                false => None,
+            })
+            .collect()
+    }
+
+    fn k_nearest(&self, pos: Vec2, k: usize) -> Vec<u32> {
+        let extent = (self.max - self.min).max(Vec2::ONE);
+        // Radius that holds about k points if they were spread evenly
+        let start = (extent.x * extent.y * k as f32 / (self.points.len().max(1) as f32 * std::f32::consts::PI)).sqrt();
+        let limit = (self.max.max(pos) - self.min.min(pos)).length();
+
+        expanding_k_nearest(self, pos, k, start, limit)
+    }
+}
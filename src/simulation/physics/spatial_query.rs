@@ -0,0 +1,104 @@
+use glam::Vec2;
+use legion::{Entity, IntoQuery, World};
+
+use super::{SpatialIndex, SpatialIndexKind};
+use crate::simulation::{Config, RigidCircle};
+
+/// Spatial lookups over a world's particles, answered in entities. Keeps its own index built
+/// from the world as it is, since the physics index lags a step behind or, with neighbour
+/// lists, many more
+pub struct SpatialQuery {
+    index: Box<dyn SpatialIndex>,
+    /// What `index` was created as, so it can be recreated when the config changes
+    kind: SpatialIndexKind,
+    cell_size: u32,
+    /// Entity and state of every id
+    entities: Vec<Entity>,
+    circles: Vec<RigidCircle>,
+    /// Build scratch
+    points: Vec<(Vec2, u32)>,
+    radii: Vec<f32>,
+    stale: bool,
+}
+
+impl SpatialQuery {
+    pub fn new(config: &Config, cell_size: u32) -> Self {
+        Self {
+            index: config.spatial_index.create(config, cell_size),
+            kind: config.spatial_index,
+            cell_size,
+            entities: Vec::new(),
+            circles: Vec::new(),
+            points: Vec::new(),
+            radii: Vec::new(),
+            stale: true,
+        }
+    }
+
+    /// Forces a rebuild before the next lookup, for when the world has changed
+    pub fn invalidate(&mut self) {
+        self.stale = true;
+    }
+
+    /// Rebuilds from `world` if it has changed since the last build
+    pub fn update(&mut self, world: &World, config: &Config, cell_size: u32) {
+        if config.spatial_index != self.kind || cell_size != self.cell_size {
+            self.index = config.spatial_index.create(config, cell_size);
+            self.kind = config.spatial_index;
+            self.cell_size = cell_size;
+            self.stale = true;
+        }
+        if !self.stale {
+            return;
+        }
+
+        self.entities.clear();
+        self.circles.clear();
+        for (entity, circ) in <(Entity, &RigidCircle)>::query().iter(world) {
+            self.entities.push(*entity);
+            self.circles.push(*circ);
+        }
+        self.points.clear();
+        self.points
+            .extend(self.circles.iter().enumerate().map(|(i, circ)| (circ.pos, i as u32)));
+        self.radii.clear();
+        self.radii.extend(self.circles.iter().map(|circ| circ.radius));
+
+        self.index.build(&self.points, &self.radii);
+        self.stale = false;
+    }
+
+    /// Up to `k` particles whose centres are closest to `pos`, nearest first
+    pub fn k_nearest(&self, pos: Vec2, k: usize) -> Vec<Entity> {
+        self.to_entities(self.index.k_nearest(pos, k))
+    }
+
+    /// Particle whose centre is closest to `pos`
+    pub fn nearest(&self, pos: Vec2) -> Option<Entity> {
+        self.index.k_nearest(pos, 1).first().map(|id| self.entities[*id as usize])
+    }
+
+    /// Particles centred inside the axis aligned box spanned by `min` and `max`
+    pub fn query_aabb(&self, min: Vec2, max: Vec2) -> Vec<Entity> {
+        self.to_entities(self.index.query_aabb(min, max))
+    }
+
+    /// Particles centred within `radius` of `pos` that `predicate` accepts
+    pub fn query_circle<F>(&self, pos: Vec2, radius: f32, mut predicate: F) -> Vec<Entity>
+    where
+        F: FnMut(Entity, &RigidCircle) -> bool,
+    {
+        let mut hits = Vec::new();
+        self.index.for_each_within(pos, radius, u32::MAX, &mut |id| {
+            let (entity, circ) = (self.entities[id as usize], &self.circles[id as usize]);
+            if predicate(entity, circ) {
+                hits.push(entity);
+            }
+        });
+        hits
+    }
+
+    fn to_entities(&self, ids: Vec<u32>) -> Vec<Entity> {
+        ids.into_iter().map(|id| self.entities[id as usize]).collect()
+    }
+}
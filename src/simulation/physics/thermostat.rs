@@ -0,0 +1,110 @@
+use std::f32::consts::TAU;
+
+use glam::{vec2, Vec2};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Particles that share a random number generator for Langevin kicks. Each chunk seeds its own,
+/// so the kicks don't depend on how the work is split between threads
+const KICK_CHUNK: usize = 1024;
+
+/// How the thermostat pulls the temperature towards its target
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThermostatKind {
+    Off,
+    /// Scales every velocity to hit the target exactly each step
+    Rescale,
+    /// Scales velocities so the temperature relaxes to the target over `coupling_time`
+    Berendsen,
+    /// Slows every particle by `friction` and kicks it randomly, which samples the canonical
+    /// ensemble instead of just fixing the mean
+    Langevin,
+}
+
+/// Temperature control, applied once the step has moved and collided the particles. Temperature
+/// is the mean translational kinetic energy per particle, spin is left alone
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Thermostat {
+    pub kind: ThermostatKind,
+    /// Target temperature
+    pub temperature: f32,
+    /// Time `Berendsen` takes to close most of the gap to the target
+    pub coupling_time: f32,
+    /// Rate `Langevin` damps velocities at, per unit time
+    pub friction: f32,
+}
+
+impl Default for Thermostat {
+    fn default() -> Self {
+        Self {
+            kind: ThermostatKind::Off,
+            temperature: 1.0,
+            coupling_time: 10.0,
+            friction: 0.1,
+        }
+    }
+}
+
+impl Thermostat {
+    pub fn is_off(&self) -> bool {
+        self.kind == ThermostatKind::Off
+    }
+
+    /// Moves the temperature of particles with velocities `vel` and radii `radius` towards the
+    /// target over `dt`. Mass goes with area. `seed` picks the random kicks
+    pub fn apply(&self, vel: &mut [Vec2], radius: &[f32], dt: f32, seed: u64) {
+        let scale = match self.kind {
+            ThermostatKind::Off => return,
+            ThermostatKind::Langevin => return self.kick(vel, radius, dt, seed),
+            ThermostatKind::Rescale => 1.0,
+            ThermostatKind::Berendsen => (dt / self.coupling_time).min(1.0),
+        };
+
+        let current = temperature(vel, radius);
+        if current <= 0.0 {
+            return;
+        }
+        let lambda = (1.0 + scale * (self.temperature / current - 1.0)).max(0.0).sqrt();
+        vel.par_iter_mut().for_each(|vel| *vel *= lambda);
+    }
+
+    /// Exact update of the Langevin equation over `dt`, stable however large the step
+    fn kick(&self, vel: &mut [Vec2], radius: &[f32], dt: f32, seed: u64) {
+        let retained = (-self.friction * dt).exp();
+        let spread = (self.temperature.max(0.0) * (1.0 - retained * retained)).sqrt();
+
+        vel.par_chunks_mut(KICK_CHUNK)
+            .zip(radius.par_chunks(KICK_CHUNK))
+            .enumerate()
+            .for_each(|(chunk, (vel, radius))| {
+                let mut rng = StdRng::seed_from_u64(seed ^ (chunk as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+                for (vel, radius) in vel.iter_mut().zip(radius) {
+                    *vel = *vel * retained + gaussian(&mut rng) * spread / *radius;
+                }
+            });
+    }
+}
+
+/// Mean translational kinetic energy per particle. Each particle has two degrees of freedom, so
+/// this is also the temperature with a unit Boltzmann constant
+pub fn temperature(vel: &[Vec2], radius: &[f32]) -> f32 {
+    if vel.is_empty() {
+        return 0.0;
+    }
+    let energy: f64 = vel
+        .par_iter()
+        .zip(radius)
+        .map(|(vel, radius)| 0.5 * (radius.powi(2) * vel.length_squared()) as f64)
+        .sum();
+    (energy / vel.len() as f64) as f32
+}
+
+/// Pair of independent standard normal samples, by the Box-Muller transform
+fn gaussian(rng: &mut StdRng) -> Vec2 {
+    let u: f32 = 1.0 - rng.gen::<f32>();
+    let angle = TAU * rng.gen::<f32>();
+    (-2.0 * u.ln()).sqrt() * vec2(angle.cos(), angle.sin())
+}
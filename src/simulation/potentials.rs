@@ -0,0 +1,189 @@
+use std::convert::TryFrom;
+
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+use super::MAX_TYPES;
+
+/// 2^(1/6), where the Lennard-Jones potential has its minimum in units of sigma
+const LJ_MINIMUM: f32 = 1.122_462;
+
+/// Interaction energy between two particles as a function of their distance
+#[derive(Clone, Copy, PartialEq, Debug, Default, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PairPotential {
+    #[default]
+    None,
+    /// `4 epsilon ((sigma / r)^12 - (sigma / r)^6)`, attracts beyond 2^(1/6) sigma and repels inside it
+    LennardJones { epsilon: f32, sigma: f32 },
+    /// `depth (1 - exp(-width (r - r0)))^2`, a bond of equilibrium length `r0`
+    Morse { depth: f32, width: f32, r0: f32 },
+    /// Weeks-Chandler-Andersen, the repulsive part of Lennard-Jones cut at its minimum. Soft spheres
+    /// of diameter about `sigma` that ignore the global cutoff
+    Wca { epsilon: f32, sigma: f32 },
+    /// Screened Coulomb `strength exp(-screening r) / r`, repulsive for positive `strength`
+    Yukawa { strength: f32, screening: f32 },
+}
+
+impl PairPotential {
+    /// Distance beyond which the potential is dropped
+    pub fn range(&self, cutoff: f32) -> f32 {
+        match *self {
+            PairPotential::None => 0.0,
+            PairPotential::Wca { sigma, .. } => LJ_MINIMUM * sigma,
+            _ => cutoff,
+        }
+    }
+
+    /// Force along the line between two particles `dist` apart, positive pushes them apart
+    #[inline]
+    pub fn force(&self, dist: f32) -> f32 {
+        match *self {
+            PairPotential::None => 0.0,
+            PairPotential::LennardJones { epsilon, sigma } | PairPotential::Wca { epsilon, sigma } => {
+                let s6 = (sigma / dist).powi(6);
+                24.0 * epsilon / dist * (2.0 * s6 * s6 - s6)
+            }
+            PairPotential::Morse { depth, width, r0 } => {
+                let e = (-width * (dist - r0)).exp();
+                -2.0 * width * depth * e * (1.0 - e)
+            }
+            PairPotential::Yukawa { strength, screening } => {
+                strength * (-screening * dist).exp() * (1.0 + screening * dist) / dist.powi(2)
+            }
+        }
+    }
+}
+
+/// Potentials between particular pairs of types, the same whichever way round the pair is.
+/// Serialized as a list of the pairs that have one, e.g.
+/// `[{ "from": 0, "to": 1, "potential": { "kind": "morse", "depth": 1, "width": 0.5, "r0": 6 } }]`
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(try_from = "Vec<PairEntry>", into = "Vec<PairEntry>")]
+pub struct PairPotentials {
+    /// `pairs[i][j]` with `i <= j` is the potential between types `i` and `j`
+    pairs: [[Option<PairPotential>; MAX_TYPES]; MAX_TYPES],
+}
+
+/// Serialized form of one of the `PairPotentials`
+#[derive(Serialize, Deserialize)]
+struct PairEntry {
+    from: u8,
+    to: u8,
+    potential: PairPotential,
+}
+
+impl TryFrom<Vec<PairEntry>> for PairPotentials {
+    type Error = String;
+
+    fn try_from(entries: Vec<PairEntry>) -> Result<Self, Self::Error> {
+        let mut pairs = PairPotentials::default();
+        for PairEntry { from, to, potential } in entries {
+            if from as usize >= MAX_TYPES || to as usize >= MAX_TYPES {
+                return Err(format!("potential between types {} and {} is out of range", from, to));
+            }
+            pairs.pairs[from.min(to) as usize][from.max(to) as usize] = Some(potential);
+        }
+        Ok(pairs)
+    }
+}
+
+impl From<PairPotentials> for Vec<PairEntry> {
+    fn from(pairs: PairPotentials) -> Self {
+        let mut entries = Vec::new();
+        for (from, row) in pairs.pairs.iter().enumerate() {
+            for (to, potential) in row.iter().enumerate() {
+                if let Some(potential) = potential {
+                    entries.push(PairEntry {
+                        from: from as u8,
+                        to: to as u8,
+                        potential: *potential,
+                    });
+                }
+            }
+        }
+        entries
+    }
+}
+
+impl PairPotentials {
+    /// Potential between types `a` and `b`, if the pair has one
+    #[inline]
+    pub fn get(&self, a: u8, b: u8) -> Option<PairPotential> {
+        self.pairs[a.min(b) as usize][a.max(b) as usize]
+    }
+
+    /// Every potential set for a pair
+    pub fn iter(&self) -> impl Iterator<Item = &PairPotential> {
+        self.pairs.iter().flatten().flatten()
+    }
+}
+
+/// Pair potentials between particle types, on top of the particle life rules
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Potentials {
+    /// Potential between every pair of types without an entry in `pairs`
+    pub global: PairPotential,
+    /// Overrides `global` between the pairs of types it has entries for
+    pub pairs: PairPotentials,
+    /// Distance beyond which potentials are dropped
+    pub cutoff: f32,
+    /// Subtract the force at the cutoff so it falls to zero there instead of jumping
+    pub shifted: bool,
+    /// Largest force a single pair can exert, keeps particles that spawn on top of each other
+    /// from being flung out. 0 leaves forces uncapped
+    pub max_force: f32,
+}
+
+impl Default for Potentials {
+    fn default() -> Self {
+        Self {
+            global: PairPotential::None,
+            pairs: PairPotentials::default(),
+            cutoff: 16.0,
+            shifted: true,
+            max_force: 10.0,
+        }
+    }
+}
+
+impl Potentials {
+    /// True when no pair of types has a potential and the stage can be skipped
+    pub fn is_inert(&self) -> bool {
+        self.global == PairPotential::None && self.pairs.iter().all(|pair| *pair == PairPotential::None)
+    }
+
+    /// Potential between types `a` and `b`
+    #[inline]
+    pub fn between(&self, a: u8, b: u8) -> PairPotential {
+        self.pairs.get(a, b).unwrap_or(self.global)
+    }
+
+    /// Longest distance any pair of types interacts over
+    pub fn range(&self) -> f32 {
+        self.pairs
+            .iter()
+            .chain(std::iter::once(&self.global))
+            .fold(0.0, |range, potential| range.max(potential.range(self.cutoff)))
+    }
+
+    /// Force on a particle of type `from` exerted by one of type `to` offset by `del`
+    #[inline]
+    pub fn force(&self, from: u8, to: u8, del: Vec2) -> Vec2 {
+        let potential = self.between(from, to);
+        let (dist, range) = (del.length(), potential.range(self.cutoff));
+        if dist <= 0.0 || dist >= range {
+            return Vec2::ZERO;
+        }
+
+        let mut magnitude = potential.force(dist);
+        if self.shifted {
+            magnitude -= potential.force(range);
+        }
+        if self.max_force > 0.0 {
+            magnitude = magnitude.clamp(-self.max_force, self.max_force);
+        }
+        -del / dist * magnitude
+    }
+}
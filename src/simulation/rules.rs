@@ -0,0 +1,63 @@
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+/// Upper bound on the number of particle types
+pub const MAX_TYPES: usize = 8;
+
+/// Particle life interaction rules between particle types
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Rules {
+    /// Number of particle types in use, at most `MAX_TYPES`
+    pub n_types: usize,
+    /// `attraction[i][j]` is how strongly type `i` is pulled towards type `j`, negative repels
+    pub attraction: [[f32; MAX_TYPES]; MAX_TYPES],
+    /// Distance beyond which particles don't interact
+    pub interaction_radius: f32,
+    /// Fraction of `interaction_radius` inside which every pair repels
+    pub beta: f32,
+    /// Scale applied to every force, 0 turns particle life off
+    pub strength: f32,
+    /// Fraction of velocity lost each tick
+    pub damping: f32,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self {
+            n_types: 1,
+            attraction: [[0.0; MAX_TYPES]; MAX_TYPES],
+            interaction_radius: 32.0,
+            beta: 0.3,
+            strength: 0.0,
+            damping: 0.0,
+        }
+    }
+}
+
+impl Rules {
+    /// True when the rules exert no force at all and the force stage can be skipped
+    pub fn is_inert(&self) -> bool {
+        self.strength == 0.0 && self.damping == 0.0
+    }
+
+    /// Force on a particle of type `from` exerted by one of type `to` offset by `del`
+    #[inline]
+    pub fn force(&self, from: u8, to: u8, del: Vec2) -> Vec2 {
+        let dist = del.length();
+        if dist <= 0.0 || dist >= self.interaction_radius {
+            return Vec2::ZERO;
+        }
+
+        let x = dist / self.interaction_radius;
+        let magnitude = match x < self.beta {
+            true => x / self.beta - 1.0,
+            false => {
+                let a = self.attraction[from as usize][to as usize];
+                a * (1.0 - (2.0 * x - 1.0 - self.beta).abs() / (1.0 - self.beta))
+            }
+        };
+
+        del / dist * magnitude * self.strength
+    }
+}
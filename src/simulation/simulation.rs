@@ -1,11 +1,29 @@
+use glam::Vec2;
 use legion::*;
+use rand::{rngs::StdRng, SeedableRng};
 
-use super::{components, config::Config, time::Time, PhysicsPipeline};
+use super::{
+    components,
+    config::Config,
+    experiment::{self, Event, Experiment, Timeline},
+    time::Time,
+    Fields, ForceCurve, History, PhysicsPipeline, Rules, SpatialQuery,
+};
+use crate::timing::timer::time_func;
 
 pub struct Simulation {
     pub world: World,
     pub resources: Resources,
     pub physics: PhysicsPipeline,
+    pub history: History,
+    pub timeline: Timeline,
+
+    /// Lookups for tools and analysis, see `spatial_query`
+    queries: SpatialQuery,
+    rng: StdRng,
+    paused: bool,
+    /// Ticks left to run while paused
+    pending_steps: u32,
 }
 
 impl Simulation {
@@ -16,24 +34,245 @@ impl Simulation {
         resources.insert(Time::default());
         resources.insert(config);
 
+        let mut rng = StdRng::seed_from_u64(config.seed);
         for _i in 0..config.n_cells {
-            world.push((
-                components::RigidCircle::new_rand(&config),
-                components::Color::new_rand(),
-            ));
+            let circ = components::RigidCircle::new_rand(&config, &mut rng);
+            let color = components::Color::for_kind(circ.kind, config.rules.n_types, &mut rng);
+            world.push((circ, color));
         }
 
         let physics = PhysicsPipeline::new(&mut world, &config);
+        let queries = SpatialQuery::new(&config, physics.tuner().cell_size);
+        let history = History::new(&config);
 
-        Simulation {
+        let mut simulation = Simulation {
             world,
             resources,
             physics,
-        }
+            history,
+            timeline: Timeline::default(),
+            queries,
+            rng,
+            paused: false,
+            pending_steps: 0,
+        };
+        simulation.record_history();
+        simulation
     }
 
+    /// Builds the experiment's starting state and runs any events scheduled for tick 0
+    pub fn from_experiment(experiment: &Experiment) -> Simulation {
+        let mut simulation = Simulation::new(experiment.config);
+        simulation.timeline = Timeline::new(experiment.events.clone());
+        simulation.run_events();
+        simulation.record_history();
+        simulation
+    }
+
+    /// Advances the simulation by one tick if the clock allows it
     pub fn update(&mut self) {
-        self.resources.get_mut::<Time>().unwrap().tick();
+        if !self.tick_due() {
+            return;
+        }
+
+        let dt = match self.adaptive_timestep() {
+            true => self.physics.stable_dt(),
+            false => self.resources.get::<Time>().unwrap().time_scale,
+        };
+        self.resources.get_mut::<Time>().unwrap().tick(dt);
         self.physics.step(&mut self.world, &mut self.resources);
+        self.queries.invalidate();
+        self.run_events();
+        self.record_history();
+    }
+
+    /// Snapshots the simulation if the history is due one
+    fn record_history(&mut self) {
+        let time = self.resources.get::<Time>().unwrap();
+        let config = self.resources.get::<Config>().unwrap();
+        let physics = &self.physics;
+        self.history
+            .record(&self.world, &time, &config, &self.rng, || physics.carryover());
+    }
+
+    fn run_events(&mut self) {
+        let tick = self.resources.get::<Time>().unwrap().tick;
+        let due: Vec<Event> = self.timeline.due(tick).cloned().collect();
+
+        for event in due {
+            self.apply_event(&event, tick);
+        }
+    }
+
+    pub fn apply_event(&mut self, event: &Event, tick: u128) {
+        match event {
+            Event::Pause => self.pause(),
+            Event::SetTimeScale { scale } => self.set_time_scale(*scale),
+            Event::SetTargetTickRate { rate } => self.set_target_tick_rate(*rate),
+            Event::Inject { count, min, max } => self.inject(*count, (*min, *max)),
+            Event::SetRules { rules } => self.set_rules(**rules),
+            Event::ExportSnapshot { path } => {
+                let path = path.replace("{tick}", &tick.to_string());
+                if let Err(err) = experiment::export_csv(&self.world, &path) {
+                    tracing::warn!("Failed to export snapshot to {}: {}", path, err);
+                }
+            }
+        }
+    }
+
+    /// Spawns `count` random particles inside `region`
+    pub fn inject(&mut self, count: u32, region: (Vec2, Vec2)) {
+        let config = *self.resources.get::<Config>().unwrap();
+        let rng = &mut self.rng;
+
+        self.world.extend(
+            (0..count)
+                .map(|_| {
+                    let circ = components::RigidCircle::new_rand_in(&config, region, rng);
+                    let color = components::Color::for_kind(circ.kind, config.rules.n_types, rng);
+                    (circ, color)
+                })
+                .collect::<Vec<_>>(),
+        );
+        self.queries.invalidate();
+    }
+
+    /// Nearest neighbour and region lookups over the particles as they are now.
+    /// Edits made directly to `world` need an `invalidate_queries` first
+    pub fn spatial_query(&mut self) -> &SpatialQuery {
+        let config = *self.resources.get::<Config>().unwrap();
+        self.queries.update(&self.world, &config, self.physics.tuner().cell_size);
+        &self.queries
+    }
+
+    pub fn invalidate_queries(&mut self) {
+        self.queries.invalidate();
+    }
+
+    fn tick_due(&mut self) -> bool {
+        if self.paused {
+            if self.pending_steps == 0 {
+                return false;
+            }
+            self.pending_steps -= 1;
+            return true;
+        }
+
+        self.resources.get::<Time>().unwrap().tick_due()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+        self.pending_steps = 0;
+    }
+
+    pub fn toggle_pause(&mut self) {
+        match self.paused {
+            true => self.resume(),
+            false => self.pause(),
+        }
+    }
+
+    /// Pauses the simulation and queues `n` ticks, run one per `update`
+    pub fn step(&mut self, n: u32) {
+        self.paused = true;
+        self.pending_steps += n;
+    }
+
+    /// `None` runs as fast as possible
+    pub fn set_target_tick_rate(&mut self, rate: Option<u32>) {
+        self.resources.get_mut::<Time>().unwrap().target_tick_rate = rate;
+    }
+
+    pub fn set_time_scale(&mut self, scale: f32) {
+        self.resources.get_mut::<Time>().unwrap().time_scale = scale.max(0.0);
+    }
+
+    /// Swaps the interaction rules, existing particles keep their types
+    pub fn set_rules(&mut self, rules: Rules) {
+        self.resources.get_mut::<Config>().unwrap().rules = rules;
+    }
+
+    /// Force curve acting on type `from` from type `to`
+    pub fn force_curve(&self, from: u8, to: u8) -> ForceCurve {
+        self.resources.get::<Config>().unwrap().force_curves.curves[from as usize][to as usize]
+    }
+
+    pub fn set_force_curve(&mut self, from: u8, to: u8, curve: ForceCurve) {
+        self.resources.get_mut::<Config>().unwrap().force_curves.curves[from as usize][to as usize] = curve;
+    }
+
+    pub fn fields(&self) -> Fields {
+        self.resources.get::<Config>().unwrap().fields
+    }
+
+    pub fn set_fields(&mut self, fields: Fields) {
+        self.resources.get_mut::<Config>().unwrap().fields = fields;
+    }
+
+    pub fn auto_tune_grid(&self) -> bool {
+        self.resources.get::<Config>().unwrap().auto_tune_grid
+    }
+
+    pub fn set_auto_tune_grid(&mut self, tune: bool) {
+        self.resources.get_mut::<Config>().unwrap().auto_tune_grid = tune;
+    }
+
+    pub fn adaptive_timestep(&self) -> bool {
+        self.resources.get::<Config>().unwrap().timestep.adaptive
+    }
+
+    pub fn set_adaptive_timestep(&mut self, adaptive: bool) {
+        self.resources.get_mut::<Config>().unwrap().timestep.adaptive = adaptive;
+    }
+
+    pub fn history_interval(&self) -> u32 {
+        self.history.interval
+    }
+
+    /// Snapshots for rewinding every `interval` ticks from now on, 0 stops recording
+    pub fn set_history_interval(&mut self, interval: u32) {
+        self.resources.get_mut::<Config>().unwrap().history_interval = interval;
+        self.history.interval = interval;
+    }
+
+    /// Restores the latest snapshot at or before `tick` and pauses.
+    /// Returns false if the history does not reach back that far
+    pub fn rewind_to(&mut self, tick: u128) -> bool {
+        time_func!(history, restore);
+
+        let snapshot = match self.history.at_or_before(tick) {
+            Some(snapshot) => snapshot,
+            None => return false,
+        };
+        snapshot.restore(
+            &mut self.world,
+            &mut self.resources.get_mut::<Time>().unwrap(),
+            &mut self.resources.get_mut::<Config>().unwrap(),
+        );
+        self.rng = snapshot.rng.clone();
+        self.physics.rewind(&self.world, &snapshot.carryover, &snapshot.config);
+        self.queries.invalidate();
+
+        self.paused = true;
+        self.pending_steps = 0;
+        true
+    }
+
+    /// Rewinds to the snapshot preceding the current tick
+    pub fn step_back(&mut self) -> bool {
+        let tick = self.resources.get::<Time>().unwrap().tick;
+        match tick {
+            0 => false,
+            _ => self.rewind_to(tick - 1),
+        }
     }
 }
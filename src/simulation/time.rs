@@ -1,13 +1,27 @@
 use std::time::{Duration, Instant};
 
 use fps_counter::FPSCounter;
+use serde::{Deserialize, Serialize};
+
+/// Adaptive dt never grows by more than this factor from one tick to the next, so a particle
+/// briefly slowing down doesn't let the step jump past the next fast approach
+const MAX_DT_GROWTH: f32 = 1.25;
 
 pub struct Time {
     pub tick: u128,
     pub start_time: Instant,
     pub last_tick_time: Instant,
 
+    /// Measured ticks per second
     pub tick_rate: usize,
+    /// Desired ticks per second, `None` runs as fast as possible
+    pub target_tick_rate: Option<u32>,
+    /// Simulated time advanced by a single tick, unless `Timestep::adaptive` picks it
+    pub time_scale: f32,
+    /// Total simulated time, the sum of every tick's dt
+    pub sim_time: f64,
+    /// Simulated time covered by the current tick
+    dt: f32,
     tick_counter: FPSCounter,
 }
 
@@ -20,6 +34,10 @@ impl Default for Time {
             start_time: now,
             last_tick_time: now,
             tick_rate: 0,
+            target_tick_rate: None,
+            time_scale: 1.0,
+            sim_time: 0.0,
+            dt: 1.0,
             tick_counter: FPSCounter::default(),
         }
     }
@@ -27,12 +45,35 @@ impl Default for Time {
 
 #[allow(dead_code)]
 impl Time {
-    pub fn tick(&mut self) {
+    /// Starts the next tick, which covers `dt` of simulated time
+    pub fn tick(&mut self, dt: f32) {
         self.tick += 1;
+        self.dt = dt;
+        self.sim_time += dt as f64;
         self.tick_rate = self.tick_counter.tick();
         self.last_tick_time = Instant::now();
     }
 
+    /// Sets the clock back to a tick recorded earlier, `dt` being the step that tick took
+    pub fn rewind(&mut self, tick: u128, sim_time: f64, dt: f32) {
+        self.tick = tick;
+        self.sim_time = sim_time;
+        self.dt = dt;
+    }
+
+    /// Whether enough wall time has passed to honour `target_tick_rate`
+    pub fn tick_due(&self) -> bool {
+        match self.target_tick_rate {
+            Some(rate) if rate > 0 => self.delta_time() >= Duration::from_secs_f64(1.0 / rate as f64),
+            _ => true,
+        }
+    }
+
+    /// Simulated time covered by the current tick
+    pub fn dt(&self) -> f32 {
+        self.dt
+    }
+
     pub fn time_since_start(&self) -> Duration {
         Instant::now() - self.start_time
     }
@@ -45,3 +86,45 @@ impl Time {
         Instant::now()
     }
 }
+
+/// How much simulated time a tick covers
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Timestep {
+    /// Pick each tick's dt from how fast particles move and accelerate relative to their size,
+    /// instead of using the time scale
+    pub adaptive: bool,
+    /// Fraction of its radius a particle may travel in a tick, the Courant number
+    pub courant: f32,
+    /// Bounds on the adaptive dt of each substep
+    pub min_dt: f32,
+    pub max_dt: f32,
+}
+
+impl Default for Timestep {
+    fn default() -> Self {
+        Self {
+            adaptive: false,
+            courant: 0.25,
+            min_dt: 0.01,
+            max_dt: 2.0,
+        }
+    }
+}
+
+impl Timestep {
+    /// Largest dt that keeps every particle within `courant` radii of where it is, given the
+    /// fastest speed and acceleration, both measured in radii. `dt` is the step just taken, which
+    /// the result can't exceed by much
+    pub fn stable_dt(&self, speed: f32, accel: f32, dt: f32) -> f32 {
+        let mut stable = self.max_dt.min(dt * MAX_DT_GROWTH);
+        if speed > 0.0 {
+            stable = stable.min(self.courant / speed);
+        }
+        // Starting from rest, half a dt squared of acceleration covers the distance
+        if accel > 0.0 {
+            stable = stable.min((2.0 * self.courant / accel).sqrt());
+        }
+        stable.clamp(self.min_dt, self.max_dt.max(self.min_dt))
+    }
+}
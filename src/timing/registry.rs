@@ -13,6 +13,7 @@ pub struct GlobalTimers {
     pub physics: PhysicsTimers,
     pub gui_render: GUITimers,
     pub sim_render: SimRenderTimers,
+    pub history: HistoryTimers,
 }
 #[derive(Default)]
 pub struct GUITimers {
@@ -22,8 +23,24 @@ pub struct GUITimers {
 #[derive(Default)]
 pub struct PhysicsTimers {
     pub step: Timer,
+    pub reorder: Timer,
+    /// Substeps of the tick before a reorder and of the tick of one
+    pub before_reorder: Timer,
+    pub after_reorder: Timer,
+    pub substep: Timer,
+    pub gather: Timer,
+    pub scatter: Timer,
     pub pos_update: Timer,
+    pub index_build: Timer,
+    pub ccd: Timer,
+    pub neighbour_list: Timer,
+    pub fields: Timer,
+    pub forces: Timer,
+    pub potentials: Timer,
+    pub long_range: Timer,
     pub col_detect: Timer,
+    pub contact_solve: Timer,
+    pub thermostat: Timer,
 }
 
 #[derive(Default)]
@@ -31,3 +48,9 @@ pub struct SimRenderTimers {
     pub render: Timer,
     pub vertex_buffer_update: Timer,
 }
+
+#[derive(Default)]
+pub struct HistoryTimers {
+    pub capture: Timer,
+    pub restore: Timer,
+}
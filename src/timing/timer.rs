@@ -1,10 +1,12 @@
 // I hate all of this
 
-use std::{fmt, time::Duration};
+use std::{cell::RefCell, fmt, time::Duration};
 
 use hdrhistogram::Histogram;
 use quanta::{Clock, Instant};
 
+use super::registry::{GlobalTimers, TIMING_DATABASE};
+
 #[allow(dead_code)]
 pub enum Resolution {
     Second,
@@ -65,31 +67,58 @@ impl Default for Timer {
     }
 }
 
-pub struct DropTimer<'a> {
+/// Picks the timer a measurement goes into
+type Target = for<'a> fn(&'a mut GlobalTimers) -> &'a mut Timer;
+
+/// Timers running on a thread and the measurements of those that already stopped
+#[derive(Default)]
+struct Pending {
+    running: u32,
+    measurements: Vec<(Target, Duration)>,
+}
+
+thread_local! {
+    static PENDING: RefCell<Pending> = RefCell::new(Pending::default());
+}
+
+/// Records the time between its creation and drop into the timer picked by `target`.
+/// Measurements wait on the thread until its outermost timer stops, so the database is locked
+/// once per step rather than once per stage, and simulations on other threads can time themselves too
+pub struct DropTimer {
     start: Instant,
-    target: &'a mut Timer,
+    target: Target,
 }
 
-impl<'a> DropTimer<'a> {
-    pub fn new(target: &'a mut Timer) -> Self {
+impl DropTimer {
+    pub fn new(target: Target) -> Self {
+        PENDING.with(|pending| pending.borrow_mut().running += 1);
         Self {
-            start: target.clock.now(),
-            target: target,
+            start: Instant::now(),
+            target,
         }
     }
 }
 
-impl<'a> Drop for DropTimer<'a> {
+impl Drop for DropTimer {
     fn drop(&mut self) {
-        self.target.update(self.target.clock.now() - self.start);
+        let delta = Instant::now() - self.start;
+        PENDING.with(|pending| {
+            let mut pending = pending.borrow_mut();
+            pending.measurements.push((self.target, delta));
+            pending.running -= 1;
+            if pending.running == 0 {
+                let mut database = TIMING_DATABASE.write();
+                for (target, delta) in pending.measurements.drain(..) {
+                    target(&mut database).update(delta);
+                }
+            }
+        });
     }
 }
 
 macro_rules! time_func {
     ($module:ident,$stage:ident) => {
-        use crate::timing::{DropTimer, TIMING_DATABASE};
-        let mut __timer_data = unsafe { TIMING_DATABASE.data_ptr().as_mut().unwrap() };
-        let __drop_timer = DropTimer::new(&mut __timer_data.$module.$stage);
+        let __drop_timer = crate::timing::DropTimer::new(|timers| &mut timers.$module.$stage);
     };
 }
 pub(crate) use time_func;